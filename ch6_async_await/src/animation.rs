@@ -0,0 +1,100 @@
+use microbit::gpio::{NUM_COLS, NUM_ROWS};
+
+use crate::{
+    display::{Frame, MAX_BRIGHTNESS},
+    time::TickDuration,
+};
+
+/// A sequence of frames, each shown for its own duration. Frames are borrowed,
+/// so an animation can live in a `static`.
+pub struct Animation<'a> {
+    frames: &'a [(Frame, TickDuration)],
+}
+
+impl<'a> Animation<'a> {
+    pub const fn new(frames: &'a [(Frame, TickDuration)]) -> Self {
+        Self { frames }
+    }
+
+    pub fn frames(&self) -> &'a [(Frame, TickDuration)] {
+        self.frames
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Repeat {
+    Times(u32),
+    /// Only stops when the `play` future is dropped.
+    Forever,
+}
+
+/// Which way the images move during a `Transition::Slide`.
+#[derive(Clone, Copy)]
+pub enum SlideDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy)]
+pub enum Transition {
+    /// Straight to the new frame.
+    Cut,
+    /// The new frame pushes the old one off the display, one column (or row)
+    /// per step.
+    Slide(SlideDirection),
+    /// Each pixel fades from its old level to its new one.
+    Fade,
+}
+
+impl Transition {
+    /// How many frames make up the transition, the last being the new frame.
+    pub fn steps(&self) -> u32 {
+        match self {
+            Transition::Cut => 1,
+            Transition::Slide(SlideDirection::Left | SlideDirection::Right) => NUM_COLS as u32,
+            Transition::Slide(SlideDirection::Up | SlideDirection::Down) => NUM_ROWS as u32,
+            Transition::Fade => MAX_BRIGHTNESS as u32,
+        }
+    }
+
+    /// The frame to show at `step` (from 1 up to `steps()`).
+    pub fn frame(&self, from: &Frame, to: &Frame, step: u32) -> Frame {
+        let step = step as usize;
+        let mut frame = [[0; NUM_COLS]; NUM_ROWS];
+        for (row, pixels) in frame.iter_mut().enumerate() {
+            for (col, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = match self {
+                    Transition::Cut => to[row][col],
+                    // Both frames side by side (or stacked), with the display
+                    // looking at a window that moves across from one to the
+                    // other
+                    Transition::Slide(SlideDirection::Left) => match col + step {
+                        c if c < NUM_COLS => from[row][c],
+                        c => to[row][c - NUM_COLS],
+                    },
+                    Transition::Slide(SlideDirection::Right) => match col + NUM_COLS - step {
+                        c if c < NUM_COLS => to[row][c],
+                        c => from[row][c - NUM_COLS],
+                    },
+                    Transition::Slide(SlideDirection::Up) => match row + step {
+                        r if r < NUM_ROWS => from[r][col],
+                        r => to[r - NUM_ROWS][col],
+                    },
+                    Transition::Slide(SlideDirection::Down) => match row + NUM_ROWS - step {
+                        r if r < NUM_ROWS => to[r][col],
+                        r => from[r - NUM_ROWS][col],
+                    },
+                    Transition::Fade => {
+                        let steps = self.steps() as usize;
+                        let from = from[row][col] as usize;
+                        let to = to[row][col] as usize;
+                        ((from * (steps - step) + to * step) / steps) as u8
+                    }
+                };
+            }
+        }
+        frame
+    }
+}
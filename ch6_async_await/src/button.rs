@@ -1,5 +1,317 @@
+use fugit::ExtU64;
+use futures::{select_biased, FutureExt};
+use heapless::Deque;
+
+use crate::{
+    gpiote::{GpioteInput, InputChannel},
+    time::{TickDuration, TickInstant, Ticker, Timer},
+};
+
 #[derive(Clone, Copy)]
 pub enum ButtonDirection {
     Left,
     Right,
+    /// Both buttons pressed together
+    Both,
+}
+
+#[derive(Clone, Copy)]
+pub enum ButtonEvent {
+    Press,
+    Release,
+    /// A short press & release, with no second press following it.
+    Click,
+    /// Two short presses in a row: reported instead of a second `Click`.
+    DoubleClick,
+    /// Reported on release, along with how long the button was held for.
+    LongPress(TickDuration),
+    /// Sent periodically while the button is held past `long_press`.
+    Repeat,
+}
+
+#[derive(Clone, Copy)]
+pub struct ButtonConfig {
+    /// How long to ignore the input for after it changes: the same idea as
+    /// `DebounceStrategy::Lockout`, but going by GPIOTE edges, not sampling.
+    pub debounce: TickDuration,
+    /// Longest gap between a release and the next press for a double-click.
+    pub double_click: TickDuration,
+    /// How long the button has to be held before it counts as a long press,
+    /// which is also when `Repeat` events start.
+    pub long_press: TickDuration,
+    pub repeat_interval: TickDuration,
+}
+
+impl Default for ButtonConfig {
+    fn default() -> Self {
+        Self {
+            debounce: 30.millis(),
+            double_click: 300.millis(),
+            long_press: 800.millis(),
+            repeat_interval: 200.millis(),
+        }
+    }
+}
+
+enum ButtonState {
+    Released,
+    Held {
+        since: TickInstant,
+        next_repeat: TickInstant,
+        /// Second press of a potential double-click
+        is_second: bool,
+    },
+    /// Released after a short press: wait a bit to see if it's a double-click
+    WaitForSecond {
+        until: TickInstant,
+    },
+}
+
+/// Turns the presses & releases of an `InputChannel` into `ButtonEvent`s.
+/// Durations are measured between the timestamped edges from the interrupt
+/// handler, so they stay accurate even if this gets polled late.
+///
+/// A single click comes out as `Press`, `Release`, then `Click` (once the
+/// double-click window has passed), and holding the button down gives
+/// `Press`, `Repeat`..., `Release`, `LongPress`.
+pub struct Button<P> {
+    input: InputChannel<P>,
+    config: ButtonConfig,
+    state: ButtonState,
+    /// Some transitions produce more than one event
+    pending: Deque<ButtonEvent, 2>,
+    /// Debounce: the input is ignored until then
+    quiet_until: TickInstant,
+}
+
+impl<P: GpioteInput> Button<P> {
+    pub fn new(mut input: InputChannel<P>, config: ButtonConfig) -> Self {
+        // The press & release times come from the queued edges
+        input.queue_events();
+        Self {
+            input,
+            config,
+            state: ButtonState::Released,
+            pending: Deque::new(),
+            quiet_until: Ticker::now(),
+        }
+    }
+
+    /// Cancel-safe: all progress is kept in `self` rather than in the future,
+    /// so it's fine to use in a `select_biased!` that may drop it.
+    pub async fn next_event(&mut self) -> ButtonEvent {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return event;
+            }
+            // Skipping the timer when there's nothing to wait for saves an
+            // extra trip through the executor
+            if Ticker::now() < self.quiet_until {
+                Timer::at(self.quiet_until).await;
+            }
+            match self.state {
+                ButtonState::Released => {
+                    self.input.wait_for_active().await;
+                    self.pressed(false);
+                }
+                ButtonState::Held {
+                    since,
+                    next_repeat,
+                    is_second,
+                } => {
+                    let released = select_biased! {
+                        _ = self.input.wait_for_inactive().fuse() => true,
+                        _ = Timer::at(next_repeat).fuse() => false,
+                    };
+                    if released {
+                        self.released(since, is_second);
+                    } else {
+                        self.state = ButtonState::Held {
+                            since,
+                            next_repeat: next_repeat + self.config.repeat_interval,
+                            is_second,
+                        };
+                        self.pending.push_back(ButtonEvent::Repeat).ok();
+                    }
+                }
+                ButtonState::WaitForSecond { until } => {
+                    let pressed = select_biased! {
+                        _ = self.input.wait_for_active().fuse() => true,
+                        _ = Timer::at(until).fuse() => false,
+                    };
+                    if pressed {
+                        self.pressed(true);
+                    } else {
+                        self.state = ButtonState::Released;
+                        self.pending.push_back(ButtonEvent::Click).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    /// When the input became active (or not), going by the timestamped edges
+    /// from the interrupt handler. Edges from the debounce period are just
+    /// bounce, so they're skipped. Falls back to the current time if there is
+    /// no matching edge, e.g. if the queue overflowed.
+    fn edge_time(&mut self, active: bool) -> TickInstant {
+        let state = if active {
+            self.input.active_level().active_state()
+        } else {
+            self.input.active_level().inactive_state()
+        };
+        let mut at = None;
+        // Drain the queue, so only newer edges are left for next time
+        while let Some(event) = self.input.try_next_event() {
+            if at.is_none() && event.level == state && event.at >= self.quiet_until {
+                at = Some(event.at);
+            }
+        }
+        at.unwrap_or_else(Ticker::now)
+    }
+
+    fn pressed(&mut self, is_second: bool) {
+        let since = self.edge_time(true);
+        self.pending.push_back(ButtonEvent::Press).ok();
+        self.state = ButtonState::Held {
+            since,
+            next_repeat: since + self.config.long_press,
+            is_second,
+        };
+        self.quiet_until = since + self.config.debounce;
+    }
+
+    fn released(&mut self, since: TickInstant, is_second: bool) {
+        let now = self.edge_time(false);
+        let held = now - since;
+        self.pending.push_back(ButtonEvent::Release).ok();
+        self.state = if held >= self.config.long_press {
+            self.pending.push_back(ButtonEvent::LongPress(held)).ok();
+            ButtonState::Released
+        } else if is_second {
+            self.pending.push_back(ButtonEvent::DoubleClick).ok();
+            ButtonState::Released
+        } else {
+            ButtonState::WaitForSecond {
+                until: now + self.config.double_click,
+            }
+        };
+        self.quiet_until = now + self.config.debounce;
+    }
+}
+
+/// Which buttons are currently held down, as far as `ButtonPair` can tell.
+struct Held {
+    left: bool,
+    right: bool,
+    /// Set once a chord has been reported, until both buttons are released
+    chord: bool,
+}
+
+impl Held {
+    fn update(&mut self, direction: ButtonDirection, event: ButtonEvent) {
+        let held = match event {
+            ButtonEvent::Press => true,
+            ButtonEvent::Release => false,
+            _ => return,
+        };
+        match direction {
+            ButtonDirection::Left => self.left = held,
+            ButtonDirection::Right => self.right = held,
+            ButtonDirection::Both => {}
+        }
+        if !self.left && !self.right {
+            self.chord = false;
+        }
+    }
+}
+
+/// Combines the left (A) and right (B) buttons into a single stream of
+/// directions. Pressing both within `chord_window` of each other gives `Both`,
+/// and neither `Left` nor `Right` is reported for that press.
+pub struct ButtonPair<L, R> {
+    left: Button<L>,
+    right: Button<R>,
+    chord_window: TickDuration,
+    held: Held,
+}
+
+impl<L: GpioteInput, R: GpioteInput> ButtonPair<L, R> {
+    pub fn new(left: Button<L>, right: Button<R>, chord_window: TickDuration) -> Self {
+        Self {
+            left,
+            right,
+            chord_window,
+            held: Held {
+                left: false,
+                right: false,
+                chord: false,
+            },
+        }
+    }
+
+    /// Reports a direction on each press, and on each `Repeat` while a single
+    /// button is held down.
+    pub async fn next_direction(&mut self) -> ButtonDirection {
+        loop {
+            let (direction, event) = select_biased! {
+                event = self.left.next_event().fuse() => (ButtonDirection::Left, event),
+                event = self.right.next_event().fuse() => (ButtonDirection::Right, event),
+            };
+            self.held.update(direction, event);
+            match event {
+                _ if self.held.chord => {}
+                ButtonEvent::Press => {
+                    // Give the other button a moment to join in
+                    let deadline = Ticker::now() + self.chord_window;
+                    let is_chord = match direction {
+                        ButtonDirection::Left => {
+                            wait_for_chord(
+                                &mut self.right,
+                                ButtonDirection::Right,
+                                &mut self.held,
+                                deadline,
+                            )
+                            .await
+                        }
+                        _ => {
+                            wait_for_chord(
+                                &mut self.left,
+                                ButtonDirection::Left,
+                                &mut self.held,
+                                deadline,
+                            )
+                            .await
+                        }
+                    };
+                    if is_chord {
+                        self.held.chord = true;
+                        return ButtonDirection::Both;
+                    }
+                    return direction;
+                }
+                ButtonEvent::Repeat => return direction,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Returns `true` if `other` gets pressed before `deadline`.
+async fn wait_for_chord<P: GpioteInput>(
+    other: &mut Button<P>,
+    direction: ButtonDirection,
+    held: &mut Held,
+    deadline: TickInstant,
+) -> bool {
+    loop {
+        let event = select_biased! {
+            event = other.next_event().fuse() => event,
+            _ = Timer::at(deadline).fuse() => return false,
+        };
+        held.update(direction, event);
+        if let ButtonEvent::Press = event {
+            return true;
+        }
+    }
 }
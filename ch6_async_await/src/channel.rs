@@ -1,86 +1,445 @@
 use core::{
-    cell::{Cell, RefCell},
+    cell::RefCell,
     future::poll_fn,
-    task::{Poll, Waker},
+    task::{Context, Poll},
 };
 
-/// Storing the `Waker` directly this time, just to see how that works.
-/// There is no more executor dependency, which is nice..
-pub struct Channel<T> {
-    item: Cell<Option<T>>,
-    waker: RefCell<Option<Waker>>,
+use critical_section::Mutex;
+use heapless::Deque;
+
+use crate::waker::{WakerList, WakerSlots};
+
+/// Most tasks that could be waiting on either end of a channel.
+const MAX_WAITING: usize = 4;
+/// Most `Receiver` handles a `Channel` can hand out at once.
+const MAX_RECEIVERS: usize = 4;
+
+/// Returned by `try_send`, handing the item back to the caller.
+pub enum TrySendError<T> {
+    Full(T),
+    /// Every `Receiver` has been dropped, so nobody would ever read it.
+    Closed(T),
+}
+
+/// Every `Receiver` has been dropped: here's your item back.
+pub struct SendError<T>(pub T);
+
+pub enum TryReceiveError {
+    Empty,
+    /// Every `Sender` has been dropped and the buffer has been drained.
+    Closed,
+}
+
+/// The buffer & wakers behind a channel, kept separate so the same logic can
+/// be wrapped up differently depending on where the channel needs to live.
+struct ChannelState<T, const N: usize> {
+    items: Deque<T, N>,
+    send_wakers: WakerList<MAX_WAITING>,
+    /// For receivers without a handle of their own (`SyncChannel`)
+    receive_wakers: WakerList<MAX_WAITING>,
+    /// One slot per `Receiver` handle
+    receiver_wakers: WakerSlots<MAX_RECEIVERS>,
+    senders: usize,
+    /// Set once the last sender/receiver handle has been dropped. There's no
+    /// re-opening a channel after that, even if a new handle comes along.
+    senders_closed: bool,
+    receivers_closed: bool,
+}
+
+impl<T, const N: usize> ChannelState<T, N> {
+    const fn new() -> Self {
+        Self {
+            items: Deque::new(),
+            send_wakers: WakerList::new(),
+            receive_wakers: WakerList::new(),
+            receiver_wakers: WakerSlots::new(),
+            senders: 0,
+            senders_closed: false,
+            receivers_closed: false,
+        }
+    }
+
+    fn add_sender(&mut self) {
+        self.senders += 1;
+    }
+
+    fn drop_sender(&mut self) {
+        self.senders -= 1;
+        if self.senders == 0 {
+            self.senders_closed = true;
+            // Let any waiting receivers find out there's nothing more coming
+            self.wake_receivers();
+        }
+    }
+
+    fn add_receiver(&mut self) -> usize {
+        match self.receiver_wakers.claim() {
+            Some(slot) => slot,
+            None => panic!("Too many receivers!"),
+        }
+    }
+
+    fn drop_receiver(&mut self, slot: usize) {
+        self.receiver_wakers.release(slot);
+        if self.receiver_wakers.in_use() == 0 {
+            self.receivers_closed = true;
+            self.send_wakers.wake_all();
+        }
+    }
+
+    fn try_send(&mut self, item: T) -> Result<(), TrySendError<T>> {
+        if self.receivers_closed {
+            return Err(TrySendError::Closed(item));
+        }
+        self.items.push_back(item).map_err(TrySendError::Full)?;
+        self.wake_receivers();
+        Ok(())
+    }
+
+    fn wake_receivers(&mut self) {
+        self.receive_wakers.wake_all();
+        self.receiver_wakers.wake_all();
+    }
+
+    /// Anything already in the buffer can still be received after the last
+    /// sender is gone.
+    fn try_receive(&mut self) -> Result<T, TryReceiveError> {
+        match self.items.pop_front() {
+            Some(item) => {
+                // There's room now: let any blocked senders have another go
+                self.send_wakers.wake_all();
+                Ok(item)
+            }
+            None if self.senders_closed => Err(TryReceiveError::Closed),
+            None => Err(TryReceiveError::Empty),
+        }
+    }
+
+    /// `item` is only taken once there is space for it in the buffer.
+    fn poll_send(
+        &mut self,
+        cx: &mut Context<'_>,
+        item: &mut Option<T>,
+    ) -> Poll<Result<(), SendError<T>>> {
+        let Some(value) = item.take() else {
+            return Poll::Ready(Ok(()));
+        };
+        match self.try_send(value) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(TrySendError::Closed(value)) => Poll::Ready(Err(SendError(value))),
+            Err(TrySendError::Full(value)) => {
+                *item = Some(value);
+                self.send_wakers.register(cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// The waker is refreshed on every `Poll::Pending`, into the receiver's
+    /// own slot if it has one.
+    fn poll_receive(&mut self, cx: &mut Context<'_>, slot: Option<usize>) -> Poll<Option<T>> {
+        match self.try_receive() {
+            Ok(item) => Poll::Ready(Some(item)),
+            Err(TryReceiveError::Closed) => Poll::Ready(None),
+            Err(TryReceiveError::Empty) => {
+                match slot {
+                    Some(slot) => self.receiver_wakers.register(slot, cx.waker()),
+                    None => self.receive_wakers.register(cx.waker()),
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A bounded FIFO channel holding up to `N` items. Senders wait for space
+/// when it's full, receivers wait for an item when it's empty.
+///
+/// Waiting tasks are woken in the order they started waiting. A woken task
+/// still has to re-check the buffer when it runs, so this is "fair" in the
+/// sense that nobody gets starved, not in the sense of a strict queue.
+///
+/// The channel closes once all of its senders (or all of its receivers) have
+/// been dropped, which lets the tasks on the other end wind down.
+///
+/// There can be several receivers, each getting a share of the items. Each one
+/// keeps its own waker slot, so they can be awaited from different tasks.
+pub struct Channel<T, const N: usize> {
+    state: RefCell<ChannelState<T, N>>,
 }
 
-impl<T> Channel<T> {
+impl<T, const N: usize> Channel<T, N> {
     pub fn new() -> Self {
         Self {
-            item: Cell::new(None),
-            waker: RefCell::new(None),
+            state: RefCell::new(ChannelState::new()),
         }
     }
 
-    pub fn get_sender(&self) -> Sender<'_, T> {
+    pub fn get_sender(&self) -> Sender<'_, T, N> {
+        self.state.borrow_mut().add_sender();
         Sender { channel: self }
     }
 
-    pub fn get_receiver(&self) -> Receiver<'_, T> {
+    /// Panics if there are already `MAX_RECEIVERS` receivers.
+    pub fn get_receiver(&self) -> Receiver<'_, T, N> {
+        let slot = self.state.borrow_mut().add_receiver();
         Receiver {
             channel: self,
-            state: ReceiverState::Init,
+            slot,
         }
     }
+}
 
-    fn send(&self, item: T) {
-        self.item.replace(Some(item));
-        if let Some(waker) = self.waker.borrow().as_ref() {
-            // Calling `wake()` consumes the waker, which means we'd have to
-            // `clone()` it first, so instead here we use `wake_by_ref()`
-            waker.wake_by_ref();
-        }
+impl<T, const N: usize> Default for Channel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Sender<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+}
+
+impl<T, const N: usize> Sender<'_, T, N> {
+    /// Waits for space in the channel if it is currently full. Fails if all
+    /// receivers have been dropped.
+    pub async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut item = Some(item);
+        poll_fn(|cx| self.channel.state.borrow_mut().poll_send(cx, &mut item)).await
     }
 
-    fn receive(&self) -> Option<T> {
-        self.item.take()
+    pub fn try_send(&self, item: T) -> Result<(), TrySendError<T>> {
+        self.channel.state.borrow_mut().try_send(item)
     }
+}
+
+impl<T, const N: usize> Clone for Sender<'_, T, N> {
+    fn clone(&self) -> Self {
+        self.channel.get_sender()
+    }
+}
 
-    fn register(&self, waker: Waker) {
-        self.waker.replace(Some(waker));
+impl<T, const N: usize> Drop for Sender<'_, T, N> {
+    fn drop(&mut self) {
+        self.channel.state.borrow_mut().drop_sender();
     }
 }
 
-pub struct Sender<'a, T> {
-    channel: &'a Channel<T>,
+pub struct Receiver<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+    slot: usize,
 }
 
-impl<T> Sender<'_, T> {
-    pub fn send(&self, item: T) {
-        self.channel.send(item);
+impl<T, const N: usize> Receiver<'_, T, N> {
+    /// Returns `None` once all senders are gone and the buffer is empty.
+    pub async fn receive(&self) -> Option<T> {
+        poll_fn(|cx| {
+            self.channel
+                .state
+                .borrow_mut()
+                .poll_receive(cx, Some(self.slot))
+        })
+        .await
+    }
+
+    pub fn try_receive(&self) -> Result<T, TryReceiveError> {
+        self.channel.state.borrow_mut().try_receive()
     }
 }
 
-enum ReceiverState {
-    Init,
-    Wait,
+impl<T, const N: usize> Clone for Receiver<'_, T, N> {
+    fn clone(&self) -> Self {
+        self.channel.get_receiver()
+    }
 }
 
-pub struct Receiver<'a, T> {
-    channel: &'a Channel<T>,
-    state: ReceiverState,
+impl<T, const N: usize> Drop for Receiver<'_, T, N> {
+    fn drop(&mut self) {
+        self.channel.state.borrow_mut().drop_receiver(self.slot);
+    }
 }
 
-impl<T> Receiver<'_, T> {
-    pub async fn receive(&mut self) -> T {
-        poll_fn(|cx| match self.state {
-            ReceiverState::Init => {
-                self.channel.register(cx.waker().clone());
-                self.state = ReceiverState::Wait;
-                Poll::Pending
-            }
-            ReceiverState::Wait => match self.channel.receive() {
-                Some(item) => Poll::Ready(item),
-                None => Poll::Pending,
-            },
+/// The same bounded channel, but with its state behind a critical section
+/// rather than a `RefCell`. This makes it `Sync`, so it can be a `static` that
+/// is shared with interrupt handlers such as `GPIOTE()` or `RTC0()`.
+/// An ISR can't wait around for space, so it should stick to `try_send`.
+///
+/// There are no sender/receiver handles here, so a `SyncChannel` never closes.
+pub struct SyncChannel<T, const N: usize> {
+    state: Mutex<RefCell<ChannelState<T, N>>>,
+}
+
+impl<T, const N: usize> SyncChannel<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(ChannelState::new())),
+        }
+    }
+
+    /// Waits for space in the channel if it is currently full.
+    pub async fn send(&self, item: T) {
+        let mut item = Some(item);
+        poll_fn(|cx| {
+            critical_section::with(|cs| self.state.borrow_ref_mut(cs).poll_send(cx, &mut item))
         })
         .await
+        .ok();
+    }
+
+    /// Safe to call from interrupt context: the receiving task gets woken
+    /// through its registered waker.
+    pub fn try_send(&self, item: T) -> Result<(), TrySendError<T>> {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).try_send(item))
+    }
+
+    pub async fn receive(&self) -> T {
+        poll_fn(|cx| {
+            critical_section::with(|cs| self.state.borrow_ref_mut(cs).poll_receive(cx, None))
+                .map(|item| item.expect("SyncChannel never closes"))
+        })
+        .await
+    }
+
+    pub fn try_receive(&self) -> Option<T> {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).try_receive()).ok()
+    }
+}
+
+impl<T, const N: usize> Default for SyncChannel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// These run on the host rather than the micro:bit:
+/// `cargo test --target x86_64-unknown-linux-gnu` (or your host's triple)
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::task::Waker;
+    use std::{
+        sync::{Arc, Mutex},
+        task::Wake,
+        vec::Vec,
+    };
+
+    use super::*;
+
+    /// Adds its id to a shared log when woken, to check the order of wakeups.
+    struct LogWaker {
+        id: usize,
+        log: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Wake for LogWaker {
+        fn wake(self: Arc<Self>) {
+            self.log.lock().unwrap().push(self.id);
+        }
+    }
+
+    fn log_waker(id: usize, log: &Arc<Mutex<Vec<usize>>>) -> Waker {
+        Waker::from(Arc::new(LogWaker {
+            id,
+            log: log.clone(),
+        }))
+    }
+
+    fn open_channel<const N: usize>() -> (ChannelState<u32, N>, usize) {
+        let mut state = ChannelState::new();
+        state.add_sender();
+        let slot = state.add_receiver();
+        (state, slot)
+    }
+
+    #[test]
+    fn items_come_out_in_fifo_order() {
+        let (mut state, _) = open_channel::<4>();
+        for item in 1..=3 {
+            assert!(state.try_send(item).is_ok());
+        }
+        for item in 1..=3 {
+            assert!(matches!(state.try_receive(), Ok(i) if i == item));
+        }
+        assert!(matches!(state.try_receive(), Err(TryReceiveError::Empty)));
+    }
+
+    #[test]
+    fn full_channel_hands_the_item_back() {
+        let (mut state, _) = open_channel::<2>();
+        assert!(state.try_send(1).is_ok());
+        assert!(state.try_send(2).is_ok());
+        assert!(matches!(state.try_send(3), Err(TrySendError::Full(3))));
+        assert!(matches!(state.try_receive(), Ok(1)));
+        assert!(state.try_send(3).is_ok());
+    }
+
+    #[test]
+    fn blocked_senders_are_woken_in_the_order_they_waited() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (mut state, _) = open_channel::<1>();
+        assert!(state.try_send(0).is_ok());
+        for id in 1..=3 {
+            let waker = log_waker(id, &log);
+            let mut item = Some(id as u32);
+            let poll = state.poll_send(&mut Context::from_waker(&waker), &mut item);
+            assert!(poll.is_pending());
+        }
+        assert!(state.try_receive().is_ok());
+        assert_eq!(*log.lock().unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn every_receiver_gets_woken_by_a_send() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (mut state, first) = open_channel::<4>();
+        let second = state.add_receiver();
+        for (id, slot) in [(1, first), (2, second)] {
+            let waker = log_waker(id, &log);
+            let poll = state.poll_receive(&mut Context::from_waker(&waker), Some(slot));
+            assert!(poll.is_pending());
+        }
+        assert!(state.try_send(7).is_ok());
+        assert_eq!(*log.lock().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn waiting_receiver_is_woken_when_last_sender_is_dropped() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (mut state, slot) = open_channel::<4>();
+        let waker = log_waker(1, &log);
+        let poll = state.poll_receive(&mut Context::from_waker(&waker), Some(slot));
+        assert!(poll.is_pending());
+        state.drop_sender();
+        assert_eq!(*log.lock().unwrap(), [1]);
+        let poll = state.poll_receive(&mut Context::from_waker(&waker), Some(slot));
+        assert!(matches!(poll, Poll::Ready(None)));
+    }
+
+    #[test]
+    fn buffer_drains_after_last_sender_is_dropped() {
+        let (mut state, _) = open_channel::<4>();
+        assert!(state.try_send(1).is_ok());
+        assert!(state.try_send(2).is_ok());
+        state.drop_sender();
+        assert!(matches!(state.try_receive(), Ok(1)));
+        assert!(matches!(state.try_receive(), Ok(2)));
+        assert!(matches!(state.try_receive(), Err(TryReceiveError::Closed)));
+    }
+
+    #[test]
+    fn send_fails_once_all_receivers_are_dropped() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (mut state, slot) = open_channel::<1>();
+        assert!(state.try_send(1).is_ok());
+        let waker = log_waker(1, &log);
+        let mut item = Some(2);
+        let poll = state.poll_send(&mut Context::from_waker(&waker), &mut item);
+        assert!(poll.is_pending());
+        state.drop_receiver(slot);
+        assert_eq!(*log.lock().unwrap(), [1]);
+        let poll = state.poll_send(&mut Context::from_waker(&waker), &mut item);
+        assert!(matches!(poll, Poll::Ready(Err(SendError(2)))));
+        assert!(matches!(state.try_send(3), Err(TrySendError::Closed(3))));
     }
 }
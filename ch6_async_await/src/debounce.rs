@@ -0,0 +1,252 @@
+use core::num::NonZeroU8;
+
+use embedded_hal::digital::{InputPin, PinState};
+use fugit::ExtU64;
+
+use crate::time::{self, TickDuration, TickInstant, Ticker};
+
+#[derive(Clone, Copy)]
+pub enum DebounceStrategy {
+    /// Take the first change straight away, then ignore the input for a while.
+    /// Quickest to respond, but a glitch gets through as a short press.
+    Lockout(TickDuration),
+    /// Count up on every high sample and down on every low one: the state only
+    /// flips once the count hits either end of `0..=samples`.
+    Integrator { samples: NonZeroU8 },
+    /// Only take a new level once it has held steady for this long.
+    Stable(TickDuration),
+}
+
+#[derive(Clone, Copy)]
+pub struct DebounceConfig {
+    pub strategy: DebounceStrategy,
+    /// How often `wait_for_change` samples the pin.
+    pub sample_period: TickDuration,
+}
+
+impl Default for DebounceConfig {
+    fn default() -> Self {
+        Self {
+            strategy: DebounceStrategy::Stable(20.millis()),
+            sample_period: 5.millis(),
+        }
+    }
+}
+
+/// Whatever each strategy needs to remember between samples.
+enum Tracking {
+    Lockout { until: TickInstant },
+    Integrator { count: u8 },
+    Stable { since: Option<TickInstant> },
+}
+
+/// Debounces any `InputPin` by sampling it, so it doesn't need interrupts.
+///
+/// From a polled state machine, call `update` on every pass of the loop. From
+/// async code, `wait_for_change` does the sampling itself.
+///
+/// `Button` doesn't go through this: it's woken by GPIOTE edges rather than
+/// sampling every few milliseconds, and times its events from the edges'
+/// timestamps, which a sampled level can't give it. This is for pins that
+/// can't (or shouldn't) have a GPIOTE channel, and for polled code.
+pub struct Debouncer<P> {
+    pin: P,
+    config: DebounceConfig,
+    state: PinState,
+    tracking: Tracking,
+}
+
+impl<P: InputPin> Debouncer<P> {
+    /// Starts from whatever level the pin is at right now.
+    pub fn new(mut pin: P, config: DebounceConfig) -> Self {
+        let state = PinState::from(pin.is_high().unwrap());
+        let tracking = match config.strategy {
+            // Nothing to lock out yet
+            DebounceStrategy::Lockout(_) => Tracking::Lockout {
+                until: TickInstant::from_ticks(0),
+            },
+            DebounceStrategy::Integrator { samples } => Tracking::Integrator {
+                count: if state == PinState::High {
+                    samples.get()
+                } else {
+                    0
+                },
+            },
+            DebounceStrategy::Stable(_) => Tracking::Stable { since: None },
+        };
+        Self {
+            pin,
+            config,
+            state,
+            tracking,
+        }
+    }
+
+    /// The debounced level.
+    pub fn state(&self) -> PinState {
+        self.state
+    }
+
+    pub fn is_high(&self) -> bool {
+        self.state == PinState::High
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.state == PinState::Low
+    }
+
+    /// Samples the pin, returning the new level if the debounced state changed.
+    pub fn update(&mut self, now: TickInstant) -> Option<PinState> {
+        let raw = PinState::from(self.pin.is_high().unwrap());
+        let changed = match (&mut self.tracking, self.config.strategy) {
+            (Tracking::Lockout { until }, DebounceStrategy::Lockout(lockout)) => {
+                if now < *until || raw == self.state {
+                    false
+                } else {
+                    *until = now + lockout;
+                    true
+                }
+            }
+            (Tracking::Integrator { count }, DebounceStrategy::Integrator { samples }) => {
+                *count = match raw {
+                    PinState::High => count.saturating_add(1).min(samples.get()),
+                    PinState::Low => count.saturating_sub(1),
+                };
+                match self.state {
+                    PinState::Low => *count == samples.get(),
+                    PinState::High => *count == 0,
+                }
+            }
+            (Tracking::Stable { since }, DebounceStrategy::Stable(stable)) => {
+                if raw == self.state {
+                    *since = None;
+                    false
+                } else {
+                    let since = *since.get_or_insert(now);
+                    now - since >= stable
+                }
+            }
+            _ => unreachable!("Tracking doesn't match the strategy"),
+        };
+        if !changed {
+            return None;
+        }
+        if let Tracking::Stable { since } = &mut self.tracking {
+            *since = None;
+        }
+        self.state = raw;
+        Some(raw)
+    }
+
+    /// Samples every `sample_period` until the debounced level changes.
+    pub async fn wait_for_change(&mut self) -> PinState {
+        loop {
+            if let Some(state) = self.update(Ticker::now()) {
+                return state;
+            }
+            time::delay(self.config.sample_period).await;
+        }
+    }
+
+    pub async fn wait_for(&mut self, state: PinState) {
+        while self.state != state {
+            self.wait_for_change().await;
+        }
+    }
+
+    pub fn into_inner(self) -> P {
+        self.pin
+    }
+}
+
+/// These run on the host rather than the micro:bit:
+/// `cargo test --target x86_64-unknown-linux-gnu` (or your host's triple)
+#[cfg(test)]
+mod tests {
+    use core::{cell::Cell, convert::Infallible};
+
+    use embedded_hal::digital::ErrorType;
+
+    use super::*;
+
+    /// A pin whose level the test sets through the `Cell`.
+    struct FakePin<'a>(&'a Cell<bool>);
+
+    impl ErrorType for FakePin<'_> {
+        type Error = Infallible;
+    }
+
+    impl InputPin for FakePin<'_> {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.0.get())
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.0.get())
+        }
+    }
+
+    fn at(millis: u64) -> TickInstant {
+        let offset: TickDuration = millis.millis();
+        TickInstant::from_ticks(0) + offset
+    }
+
+    fn debouncer(level: &Cell<bool>, strategy: DebounceStrategy) -> Debouncer<FakePin<'_>> {
+        let config = DebounceConfig {
+            strategy,
+            ..Default::default()
+        };
+        Debouncer::new(FakePin(level), config)
+    }
+
+    #[test]
+    fn lockout_takes_the_first_change_then_ignores_the_input() {
+        let level = Cell::new(true);
+        let mut debouncer = debouncer(&level, DebounceStrategy::Lockout(20.millis()));
+        level.set(false);
+        assert_eq!(debouncer.update(at(100)), Some(PinState::Low));
+        level.set(true);
+        assert_eq!(debouncer.update(at(105)), None);
+        assert!(debouncer.is_low());
+        assert_eq!(debouncer.update(at(120)), Some(PinState::High));
+    }
+
+    #[test]
+    fn integrator_flips_once_the_count_reaches_either_end() {
+        let level = Cell::new(false);
+        let samples = NonZeroU8::new(3).unwrap();
+        let mut debouncer = debouncer(&level, DebounceStrategy::Integrator { samples });
+        level.set(true);
+        assert_eq!(debouncer.update(at(0)), None);
+        assert_eq!(debouncer.update(at(5)), None);
+        // A glitch low only takes one off the count
+        level.set(false);
+        assert_eq!(debouncer.update(at(10)), None);
+        level.set(true);
+        assert_eq!(debouncer.update(at(15)), None);
+        assert_eq!(debouncer.update(at(20)), Some(PinState::High));
+        level.set(false);
+        assert_eq!(debouncer.update(at(25)), None);
+        assert_eq!(debouncer.update(at(30)), None);
+        assert_eq!(debouncer.update(at(35)), Some(PinState::Low));
+    }
+
+    #[test]
+    fn stable_waits_for_the_level_to_hold() {
+        let level = Cell::new(true);
+        let mut debouncer = debouncer(&level, DebounceStrategy::Stable(20.millis()));
+        level.set(false);
+        assert_eq!(debouncer.update(at(0)), None);
+        // Bouncing back starts the wait over
+        level.set(true);
+        assert_eq!(debouncer.update(at(5)), None);
+        level.set(false);
+        assert_eq!(debouncer.update(at(10)), None);
+        assert_eq!(debouncer.update(at(29)), None);
+        assert_eq!(debouncer.update(at(30)), Some(PinState::Low));
+        // And again for the way back
+        level.set(true);
+        assert_eq!(debouncer.update(at(35)), None);
+        assert_eq!(debouncer.update(at(55)), Some(PinState::High));
+    }
+}
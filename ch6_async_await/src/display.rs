@@ -0,0 +1,347 @@
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embedded_hal::digital::{OutputPin, PinState};
+use microbit::{
+    gpio::{NUM_COLS, NUM_ROWS},
+    hal::gpio::{Output, Pin, PushPull},
+    pac::{interrupt, Interrupt, NVIC, TIMER2},
+};
+
+use crate::{
+    animation::{Animation, Repeat, Transition},
+    font::{self, GLYPH_WIDTH},
+    time::{self, TickDuration, Ticker, Timer},
+};
+
+/// Brightness levels go from 0 (off) up to this.
+pub const MAX_BRIGHTNESS: u8 = 9;
+
+/// `frame[row][col]`, with a brightness from 0 to `MAX_BRIGHTNESS`.
+pub type Frame = [[u8; NUM_COLS]; NUM_ROWS];
+
+/// `frame[row][col]`, with `true` meaning lit (at `MAX_BRIGHTNESS`).
+pub type BitFrame = [[bool; NUM_COLS]; NUM_ROWS];
+
+/// How long each row gets, in µs: 5 rows at 3 ms each redraws the whole
+/// matrix at ~67 Hz, which is fast enough not to flicker.
+const ROW_MICROS: u32 = 3000;
+
+/// How long an LED stays lit for at each brightness level, in µs out of
+/// `ROW_MICROS`. Eyes are much better at telling dim levels apart than bright
+/// ones, so the steps get bigger as they go up.
+const LEVEL_MICROS: [u32; MAX_BRIGHTNESS as usize + 1] =
+    [0, 60, 120, 210, 360, 570, 870, 1320, 1980, 3000];
+
+/// Default time for text to move along by one column.
+const SCROLL_STEP: TickDuration = TickDuration::millis(100);
+
+/// What a pixel actually gets shown at, after the global brightness.
+fn scaled(level: u8, brightness: u8) -> u8 {
+    let level = level.min(MAX_BRIGHTNESS) as u16;
+    (level * brightness as u16 / MAX_BRIGHTNESS as u16) as u8
+}
+
+struct DisplayPins {
+    cols: [Pin<Output<PushPull>>; NUM_COLS],
+    rows: [Pin<Output<PushPull>>; NUM_ROWS],
+}
+
+/// Everything the `TIMER2()` interrupt handler needs to refresh the matrix.
+struct Refresh {
+    pins: DisplayPins,
+    timer: TIMER2,
+    frame: Frame,
+    /// Scales every pixel, from 0 to `MAX_BRIGHTNESS`
+    brightness: u8,
+    /// The row that's lit right now, and its levels after the brightness
+    row: usize,
+    levels: [u8; NUM_COLS],
+    /// Highest level whose columns have already been switched off
+    done: u8,
+    /// Level to switch off at the next COMPARE0, or `None` for the end of the
+    /// row
+    pending: Option<u8>,
+}
+
+static REFRESH: Mutex<RefCell<Option<Refresh>>> = Mutex::new(RefCell::new(None));
+
+impl Refresh {
+    /// Off with the current row, and on with the next one.
+    fn next_row(&mut self) {
+        // Off before the columns change, so nothing ghosts onto the next row
+        self.pins.rows[self.row].set_low().ok();
+        self.row = (self.row + 1) % NUM_ROWS;
+        let brightness = self.brightness;
+        self.levels = self.frame[self.row].map(|level| scaled(level, brightness));
+        for (pin, level) in self.pins.cols.iter_mut().zip(self.levels) {
+            // Columns are active low
+            pin.set_state(PinState::from(level == 0)).ok();
+        }
+        self.pins.rows[self.row].set_high().ok();
+        self.done = 0;
+        self.timer.tasks_clear.write(|w| unsafe { w.bits(1) });
+    }
+
+    fn on_compare(&mut self) {
+        match self.pending {
+            Some(level) => {
+                for (pin, col_level) in self.pins.cols.iter_mut().zip(self.levels) {
+                    if col_level == level {
+                        pin.set_high().ok();
+                    }
+                }
+                self.done = level;
+            }
+            None => self.next_row(),
+        }
+    }
+
+    /// Sets COMPARE0 for whatever comes next in this row. If the timer is
+    /// already past that (interrupts were held off for a while), it gets done
+    /// straight away instead, rather than waiting for the timer to wrap.
+    fn schedule_next(&mut self) {
+        loop {
+            let done = self.done;
+            self.pending = self
+                .levels
+                .iter()
+                .copied()
+                .filter(|level| *level > done && LEVEL_MICROS[*level as usize] < ROW_MICROS)
+                .min();
+            let at = self
+                .pending
+                .map_or(ROW_MICROS, |level| LEVEL_MICROS[level as usize]);
+            self.timer.cc[0].write(|w| unsafe { w.bits(at) });
+            self.timer.tasks_capture[1].write(|w| unsafe { w.bits(1) });
+            if self.timer.cc[1].read().bits() < at {
+                return;
+            }
+            self.timer.events_compare[0].write(|w| w);
+            self.on_compare();
+        }
+    }
+}
+
+/// Drives the whole 5x5 LED matrix.
+///
+/// The LEDs are wired up in a grid: an LED is lit when its row pin is high and
+/// its column pin is low. That only allows one pattern of columns at a time, so
+/// the rows get lit up one after another, quickly enough that they all look
+/// like they're on.
+///
+/// The refreshing is done by the `TIMER2()` interrupt handler, so it keeps
+/// going no matter what the tasks are up to, and never wakes the executor.
+/// Dimmer LEDs get switched off part of the way through their row's time, with
+/// an extra interrupt for each distinct level in the row.
+///
+/// Anything can draw into the frame buffer through a shared reference, and
+/// the change shows up from the next row on.
+pub struct Display {
+    /// There's only ever one: it owns TIMER2
+    _timer: (),
+}
+
+impl Display {
+    /// Takes the pins from `board.display_pins.degrade()`.
+    pub fn new(
+        mut cols: [Pin<Output<PushPull>>; NUM_COLS],
+        mut rows: [Pin<Output<PushPull>>; NUM_ROWS],
+        timer: TIMER2,
+    ) -> Self {
+        for col in cols.iter_mut() {
+            col.set_high().ok();
+        }
+        for row in rows.iter_mut() {
+            row.set_low().ok();
+        }
+        // 32-bit timer at 1 MHz
+        timer.mode.write(|w| w.mode().timer());
+        timer.bitmode.write(|w| w.bitmode()._32bit());
+        timer.prescaler.write(|w| unsafe { w.prescaler().bits(4) });
+        timer.intenset.write(|w| w.compare0().set());
+        let mut refresh = Refresh {
+            pins: DisplayPins { cols, rows },
+            timer,
+            frame: [[0; NUM_COLS]; NUM_ROWS],
+            brightness: MAX_BRIGHTNESS,
+            // So that the first row to be lit is row 0
+            row: NUM_ROWS - 1,
+            levels: [0; NUM_COLS],
+            done: 0,
+            pending: None,
+        };
+        refresh.next_row();
+        refresh.schedule_next();
+        refresh.timer.tasks_start.write(|w| unsafe { w.bits(1) });
+        critical_section::with(|cs| {
+            REFRESH.replace(cs, Some(refresh));
+        });
+        // SAFETY:
+        // We aren't using mask-based critical sections.
+        unsafe { NVIC::unmask(Interrupt::TIMER2); }
+        Self { _timer: () }
+    }
+
+    fn with_refresh<R>(&self, f: impl FnOnce(&mut Refresh) -> R) -> R {
+        critical_section::with(|cs| f(REFRESH.borrow_ref_mut(cs).as_mut().unwrap()))
+    }
+
+    pub fn show(&self, frame: Frame) {
+        self.with_refresh(|refresh| refresh.frame = frame);
+    }
+
+    /// Shows every lit pixel at `MAX_BRIGHTNESS`.
+    pub fn show_bits(&self, frame: BitFrame) {
+        self.show(frame.map(|row| row.map(|lit| if lit { MAX_BRIGHTNESS } else { 0 })));
+    }
+
+    pub fn clear(&self) {
+        self.show([[0; NUM_COLS]; NUM_ROWS]);
+    }
+
+    pub fn frame(&self) -> Frame {
+        self.with_refresh(|refresh| refresh.frame)
+    }
+
+    /// Levels above `MAX_BRIGHTNESS` are clamped.
+    pub fn set(&self, row: usize, col: usize, level: u8) {
+        self.with_refresh(|refresh| refresh.frame[row][col] = level.min(MAX_BRIGHTNESS));
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> u8 {
+        self.with_refresh(|refresh| refresh.frame[row][col])
+    }
+
+    pub fn set_brightness(&self, brightness: u8) {
+        self.with_refresh(|refresh| refresh.brightness = brightness.min(MAX_BRIGHTNESS));
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.with_refresh(|refresh| refresh.brightness)
+    }
+
+    /// Shows a single character from the built-in font.
+    pub fn show_char(&self, c: char) {
+        let glyph = font::glyph(c);
+        let mut frame = [[0; NUM_COLS]; NUM_ROWS];
+        for (row, pixels) in frame.iter_mut().enumerate() {
+            for (col, pixel) in pixels.iter_mut().enumerate() {
+                if font::is_lit(glyph, row, col) {
+                    *pixel = MAX_BRIGHTNESS;
+                }
+            }
+        }
+        self.show(frame);
+    }
+
+    /// Scrolls `text` in from the right until it has gone off the left edge.
+    pub async fn scroll(&self, text: &str) {
+        self.scroll_with_step(text, SCROLL_STEP).await;
+    }
+
+    /// Scrolls `text` across, moving along one column every `step`.
+    ///
+    /// Dropping this future part of the way through (e.g. in a `select_biased!`
+    /// that went the other way) stops the scroll and clears the display.
+    pub async fn scroll_with_step(&self, text: &str, step: TickDuration) {
+        let _clear = ClearOnDrop(self);
+        // A blank column between characters, then enough blank columns at the
+        // end to scroll the last one off
+        let columns = text
+            .chars()
+            .flat_map(|c| {
+                let glyph = font::glyph(c);
+                (0..=GLYPH_WIDTH).map(move |col| {
+                    let mut column = [0; NUM_ROWS];
+                    if col < GLYPH_WIDTH {
+                        for (row, pixel) in column.iter_mut().enumerate() {
+                            if font::is_lit(glyph, row, col) {
+                                *pixel = MAX_BRIGHTNESS;
+                            }
+                        }
+                    }
+                    column
+                })
+            })
+            .chain(core::iter::repeat_n([0; NUM_ROWS], NUM_COLS));
+        let mut frame = [[0; NUM_COLS]; NUM_ROWS];
+        for column in columns {
+            for (pixels, pixel) in frame.iter_mut().zip(column) {
+                pixels.copy_within(1.., 0);
+                pixels[NUM_COLS - 1] = pixel;
+            }
+            self.show(frame);
+            time::delay(step).await;
+        }
+    }
+
+    /// Plays through an animation's frames. Each frame's time is counted from
+    /// when the previous one was due, so delays don't add up over a long run.
+    ///
+    /// An animation with no frames returns straight away. One whose frames
+    /// all take no time would never give anything else a chance to run, so it
+    /// just shows the last frame and returns, whatever `repeat` says.
+    pub async fn play(&self, animation: &Animation<'_>, repeat: Repeat) {
+        let frames = animation.frames();
+        let Some((last, _)) = frames.last() else {
+            return;
+        };
+        if frames.iter().all(|(_, duration)| duration.ticks() == 0) {
+            self.show(*last);
+            return;
+        }
+        let mut next = Ticker::now();
+        let mut remaining = repeat;
+        loop {
+            match remaining {
+                Repeat::Times(0) => return,
+                Repeat::Times(n) => remaining = Repeat::Times(n - 1),
+                Repeat::Forever => {}
+            }
+            for (frame, duration) in frames {
+                self.show(*frame);
+                next += *duration;
+                Timer::at(next).await;
+            }
+        }
+    }
+
+    /// Moves from whatever is showing to `frame`, taking about `duration`.
+    pub async fn transition(&self, frame: Frame, transition: Transition, duration: TickDuration) {
+        let from = self.frame();
+        let steps = transition.steps();
+        let start = Ticker::now();
+        for step in 1..=steps {
+            self.show(transition.frame(&from, &frame, step));
+            let elapsed = duration.ticks() * step as u64 / steps as u64;
+            Timer::at(start + TickDuration::from_ticks(elapsed)).await;
+        }
+    }
+}
+
+/// Blanks the display when a scroll finishes or gets cancelled, so it doesn't
+/// leave half a character behind.
+struct ClearOnDrop<'a>(&'a Display);
+
+impl Drop for ClearOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[interrupt]
+fn TIMER2() {
+    critical_section::with(|cs| {
+        if let Some(refresh) = REFRESH.borrow_ref_mut(cs).as_mut() {
+            if refresh.timer.events_compare[0].read().bits() != 0 {
+                refresh.timer.events_compare[0].write(|w| w);
+                refresh.on_compare();
+                refresh.schedule_next();
+            }
+            // Dummy read to ensure the event flag clears
+            // (see nRF52833 Product Specification section 6.1.8)
+            let _ = refresh.timer.events_compare[0].read().bits();
+        }
+    });
+}
@@ -0,0 +1,82 @@
+use core::{cell::RefCell, future::poll_fn, task::Poll};
+
+use critical_section::Mutex;
+
+use crate::waker::WakerList;
+
+/// Most tasks that can be waiting on an `EventGroup` at once.
+const MAX_WAITING: usize = 4;
+
+struct EventGroupState {
+    bits: u32,
+    waiters: WakerList<MAX_WAITING>,
+}
+
+/// A group of 32 event flags. Interrupt handlers (or tasks) set bits, and
+/// tasks wait for any or all of the bits in a mask.
+///
+/// The waits clear the bits they were waiting on before returning, so each
+/// event gets handled once.
+pub struct EventGroup {
+    state: Mutex<RefCell<EventGroupState>>,
+}
+
+impl EventGroup {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(EventGroupState {
+                bits: 0,
+                waiters: WakerList::new(),
+            })),
+        }
+    }
+
+    /// Safe to call from interrupt context.
+    pub fn set(&self, mask: u32) {
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            state.bits |= mask;
+            state.waiters.wake_all();
+        });
+    }
+
+    pub fn clear(&self, mask: u32) {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).bits &= !mask);
+    }
+
+    pub fn get(&self) -> u32 {
+        critical_section::with(|cs| self.state.borrow_ref(cs).bits)
+    }
+
+    /// Returns whichever bits of `mask` were set.
+    pub async fn wait_any(&self, mask: u32) -> u32 {
+        self.wait(mask, |bits| bits & mask != 0).await
+    }
+
+    pub async fn wait_all(&self, mask: u32) -> u32 {
+        self.wait(mask, |bits| bits & mask == mask).await
+    }
+
+    async fn wait(&self, mask: u32, is_ready: impl Fn(u32) -> bool) -> u32 {
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                let mut state = self.state.borrow_ref_mut(cs);
+                if is_ready(state.bits) {
+                    let bits = state.bits & mask;
+                    state.bits &= !mask;
+                    Poll::Ready(bits)
+                } else {
+                    state.waiters.register(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+}
+
+impl Default for EventGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -6,7 +6,7 @@ use core::{
 };
 
 use cortex_m::asm;
-use heapless::mpmc::Q4;
+use heapless::mpmc::Q8;
 use rtt_target::rprintln;
 
 /// An alternative to storing the waker: just extract the task information
@@ -61,11 +61,15 @@ pub fn wake_task(task_id: usize) {
     }
 }
 
-static TASK_ID_READY: Q4<usize> = Q4::new();
+static TASK_ID_READY: Q8<usize> = Q8::new();
 static NUM_TASKS: AtomicUsize = AtomicUsize::new(0);
 
 pub fn run_tasks(tasks: &mut [Pin<&mut dyn Future<Output = ()>>]) -> ! {
+    assert!(tasks.len() <= u32::BITS as usize, "Too many tasks!");
     NUM_TASKS.store(tasks.len(), Ordering::Relaxed);
+    // A future that has completed must not be polled again, but a stale waker
+    // could still queue it up: keep track of the ones that are done.
+    let mut finished: u32 = 0;
 
     // everybody gets one run to start...
     for task_id in 0..tasks.len() {
@@ -78,10 +82,18 @@ pub fn run_tasks(tasks: &mut [Pin<&mut dyn Future<Output = ()>>]) -> ! {
                 rprintln!("Bad task id {}!", task_id);
                 continue;
             }
+            if finished & (1 << task_id) != 0 {
+                continue;
+            }
             rprintln!("Running task {}", task_id);
-            let _ = tasks[task_id]
+            if tasks[task_id]
                 .as_mut()
-                .poll(&mut Context::from_waker(&get_waker(task_id)));
+                .poll(&mut Context::from_waker(&get_waker(task_id)))
+                .is_ready()
+            {
+                rprintln!("Task {} finished", task_id);
+                finished |= 1 << task_id;
+            }
         }
         rprintln!("No tasks ready, going to sleep...");
         asm::wfi();
@@ -1,47 +1,226 @@
 use core::{
     future::poll_fn,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
     task::Poll,
 };
 
 use embedded_hal::digital::{InputPin, PinState};
 use microbit::{
     hal::{
-        gpio::{Floating, Input, Pin},
-        gpiote::Gpiote,
+        gpio::{p0, p1, Floating, Input, Pin, Port},
+        gpiote::{Gpiote, GpioteInputPin},
     },
-    pac::{interrupt, Interrupt, NVIC},
+    pac::{interrupt, Interrupt, NVIC, P0, P1},
 };
 
-use crate::executor::{wake_task, ExtWaker};
+use crate::{
+    channel::SyncChannel,
+    executor::{wake_task, ExtWaker},
+    sense,
+    time::{TickInstant, Ticker},
+};
+
+const NUM_CHANNELS: usize = 8;
+/// Bit `n` is set while GPIOTE channel `n` belongs to an `InputChannel`.
+static CHANNELS_IN_USE: AtomicU8 = AtomicU8::new(0);
+
+/// All of the GPIOTE channels are already in use.
+#[derive(Debug)]
+pub struct ChannelsExhausted;
+
+/// Claims the lowest-numbered free channel. Besides `InputChannel`, drivers
+/// that wire a channel up through PPI (e.g. `TouchPad`) get theirs here too.
+pub fn alloc_channel() -> Result<usize, ChannelsExhausted> {
+    let in_use = CHANNELS_IN_USE
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |in_use| {
+            let channel_id = (!in_use).trailing_zeros();
+            (channel_id < NUM_CHANNELS as u32).then(|| in_use | 1 << channel_id)
+        })
+        .map_err(|_| ChannelsExhausted)?;
+    Ok((!in_use).trailing_zeros() as usize)
+}
 
-const MAX_CHANNELS_USED: usize = 2;
-static NEXT_CHANNEL: AtomicUsize = AtomicUsize::new(0);
+pub fn free_channel(channel_id: usize) {
+    CHANNELS_IN_USE.fetch_and(!(1 << channel_id), Ordering::Relaxed);
+}
+
+/// An edge seen by the GPIOTE interrupt handler.
+#[derive(Clone, Copy)]
+pub struct InputEvent {
+    /// Pin level just after the edge
+    pub level: PinState,
+    /// When the interrupt handler saw it
+    pub at: TickInstant,
+}
 
-pub struct InputChannel {
-    pin: Pin<Input<Floating>>,
+#[derive(Clone, Copy)]
+pub enum Edge {
+    Rising,
+    Falling,
+    Any,
+}
+
+/// Which pin level counts as "active" (e.g. pressed) for an input. The
+/// micro:bit buttons have external pull-ups, so they're active-low.
+#[derive(Clone, Copy)]
+pub enum ActiveLevel {
+    Low,
+    High,
+}
+
+impl ActiveLevel {
+    pub fn active_state(self) -> PinState {
+        match self {
+            ActiveLevel::Low => PinState::Low,
+            ActiveLevel::High => PinState::High,
+        }
+    }
+
+    pub fn inactive_state(self) -> PinState {
+        !self.active_state()
+    }
+
+    /// The edge on which the input becomes active.
+    pub fn activating_edge(self) -> Edge {
+        match self {
+            ActiveLevel::Low => Edge::Falling,
+            ActiveLevel::High => Edge::Rising,
+        }
+    }
+
+    pub fn deactivating_edge(self) -> Edge {
+        match self {
+            ActiveLevel::Low => Edge::Rising,
+            ActiveLevel::High => Edge::Falling,
+        }
+    }
+}
+
+/// An input pin that can be hooked up to GPIOTE, in any input mode.
+///
+/// The HAL's own `GpioteInputPin` only covers degraded pins (`Pin<Input<_>>`),
+/// so this also covers typed pins (e.g. `P0_14<Input<Floating>>`), which then
+/// don't need to be degraded first.
+pub trait GpioteInput: InputPin {
+    fn port(&self) -> Port;
+    fn pin(&self) -> u8;
+}
+
+impl<MODE> GpioteInput for Pin<Input<MODE>> {
+    fn port(&self) -> Port {
+        Pin::port(self)
+    }
+
+    fn pin(&self) -> u8 {
+        Pin::pin(self)
+    }
+}
+
+macro_rules! typed_gpiote_inputs {
+    ($module:ident, $port:expr, [$($pin:ident: $i:literal),+ $(,)?]) => {
+        $(
+            impl<MODE> GpioteInput for $module::$pin<Input<MODE>> {
+                fn port(&self) -> Port {
+                    $port
+                }
+
+                fn pin(&self) -> u8 {
+                    $i
+                }
+            }
+        )+
+    };
+}
+
+typed_gpiote_inputs!(p0, Port::Port0, [
+    P0_00: 0, P0_01: 1, P0_02: 2, P0_03: 3, P0_04: 4, P0_05: 5, P0_06: 6, P0_07: 7,
+    P0_08: 8, P0_09: 9, P0_10: 10, P0_11: 11, P0_12: 12, P0_13: 13, P0_14: 14, P0_15: 15,
+    P0_16: 16, P0_17: 17, P0_18: 18, P0_19: 19, P0_20: 20, P0_21: 21, P0_22: 22, P0_23: 23,
+    P0_24: 24, P0_25: 25, P0_26: 26, P0_27: 27, P0_28: 28, P0_29: 29, P0_30: 30, P0_31: 31,
+]);
+typed_gpiote_inputs!(p1, Port::Port1, [
+    P1_00: 0, P1_01: 1, P1_02: 2, P1_03: 3, P1_04: 4, P1_05: 5, P1_06: 6, P1_07: 7,
+    P1_08: 8, P1_09: 9,
+]);
+
+/// Just where a `GpioteInput` is, which is all the HAL needs to set up a
+/// channel for it.
+struct PinLocation<'a, P>(&'a P);
+
+impl<P: GpioteInput> GpioteInputPin for PinLocation<'_, P> {
+    fn pin(&self) -> u8 {
+        GpioteInput::pin(self.0)
+    }
+
+    fn port(&self) -> Port {
+        GpioteInput::port(self.0)
+    }
+}
+
+/// Takes any `GpioteInput` pin, degraded or typed.
+///
+/// Dropping an `InputChannel` disables its GPIOTE channel & interrupt, and
+/// releases the channel so it can be used again.
+pub struct InputChannel<P = Pin<Input<Floating>>> {
+    pin: P,
     channel_id: usize,
+    active_level: ActiveLevel,
 }
 
-impl InputChannel {
-    pub fn new(pin: Pin<Input<Floating>>, gpiote: &Gpiote) -> Self {
-        let channel_id = NEXT_CHANNEL.fetch_add(1, Ordering::Relaxed);
+impl<P: GpioteInput> InputChannel<P> {
+    /// Inputs start out active-low, like the micro:bit buttons.
+    pub fn new(pin: P, gpiote: &Gpiote) -> Result<Self, ChannelsExhausted> {
+        let channel_id = alloc_channel()?;
         let channel = match channel_id {
             0 => gpiote.channel0(),
             1 => gpiote.channel1(),
-            MAX_CHANNELS_USED.. => todo!("Setup more channels!"),
+            2 => gpiote.channel2(),
+            3 => gpiote.channel3(),
+            4 => gpiote.channel4(),
+            5 => gpiote.channel5(),
+            6 => gpiote.channel6(),
+            7 => gpiote.channel7(),
+            NUM_CHANNELS.. => unreachable!(),
         };
-        channel.input_pin(&pin).toggle().enable_interrupt();
+        channel
+            .input_pin(&PinLocation(&pin))
+            .toggle()
+            .enable_interrupt();
         // SAFETY:
         // We aren't using mask-based critical sections.
         unsafe { NVIC::unmask(Interrupt::GPIOTE); }
-        Self {
+        // Whatever's left over from the channel's previous owner isn't ours
+        while EVENTS[channel_id].try_receive().is_some() {}
+        Ok(Self {
             pin,
             channel_id,
-        }
+            active_level: ActiveLevel::Low,
+        })
     }
 
+    pub fn set_active_level(&mut self, active_level: ActiveLevel) {
+        self.active_level = active_level;
+    }
+
+    pub fn active_level(&self) -> ActiveLevel {
+        self.active_level
+    }
+
+    pub fn is_active(&mut self) -> bool {
+        PinState::from(self.pin.is_high().unwrap()) == self.active_level.active_state()
+    }
+
+    pub async fn wait_for_active(&mut self) {
+        self.wait_for(self.active_level.active_state()).await
+    }
+
+    pub async fn wait_for_inactive(&mut self) {
+        self.wait_for(self.active_level.inactive_state()).await
+    }
+
+    /// Level-based: returns straight away if the pin is already in `ready_state`.
     pub async fn wait_for(&mut self, ready_state: PinState) {
+        self.set_polarity(Edge::Any);
         poll_fn(|cx| {
             if ready_state == PinState::from(self.pin.is_high().unwrap()) {
                 Poll::Ready(())
@@ -52,20 +231,146 @@ impl InputChannel {
         })
         .await
     }
+
+    pub async fn wait_for_rising_edge(&mut self) {
+        self.wait_for_edge(Edge::Rising).await
+    }
+
+    pub async fn wait_for_falling_edge(&mut self) {
+        self.wait_for_edge(Edge::Falling).await
+    }
+
+    pub async fn wait_for_any_edge(&mut self) {
+        self.wait_for_edge(Edge::Any).await
+    }
+
+    /// Edge-based: only an edge that happens after this is called counts, no
+    /// matter what level the pin is at right now.
+    pub async fn wait_for_edge(&mut self, edge: Edge) {
+        self.set_polarity(edge);
+        EDGE_SEEN[self.channel_id].store(false, Ordering::Relaxed);
+        poll_fn(|cx| {
+            // Register first, so an edge can't sneak in between the check and
+            // the registration without waking us.
+            WAKE_TASKS[self.channel_id].store(cx.waker().task_id(), Ordering::Relaxed);
+            if EDGE_SEEN[self.channel_id].swap(false, Ordering::Relaxed) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Timestamped edges, oldest first. Going by these rather than when the
+    /// task gets around to looking keeps press timing accurate, even if the
+    /// task is polled late.
+    ///
+    /// Edges are only seen in both directions while the polarity is `Any`, as
+    /// set by `wait_for` & `next_event`.
+    ///
+    /// Edges are only queued once asked for, by `queue_events` or the first
+    /// call to this (or `try_next_event`), so code that only uses the
+    /// `wait_for*` methods never has stale edges piling up.
+    pub async fn next_event(&mut self) -> InputEvent {
+        self.queue_events();
+        EVENTS[self.channel_id].receive().await
+    }
+
+    pub fn try_next_event(&mut self) -> Option<InputEvent> {
+        self.queue_events();
+        EVENTS[self.channel_id].try_receive()
+    }
+
+    /// Starts queueing timestamped edges for `next_event`, without waiting
+    /// for one. Queueing carries on until the `InputChannel` is dropped.
+    pub fn queue_events(&mut self) {
+        self.set_polarity(Edge::Any);
+        QUEUE_EVENTS[self.channel_id].store(true, Ordering::Relaxed);
+    }
+
+    fn set_polarity(&self, edge: Edge) {
+        // SAFETY:
+        // Only this channel's CONFIG register is touched.
+        let gpiote = unsafe { &*microbit::pac::GPIOTE::ptr() };
+        gpiote.config[self.channel_id].modify(|_, w| match edge {
+            Edge::Rising => w.polarity().lo_to_hi(),
+            Edge::Falling => w.polarity().hi_to_lo(),
+            Edge::Any => w.polarity().toggle(),
+        });
+    }
 }
 
-const INVALID_TASK_ID: usize = 0xFFFF_FFFF;
-const DEFAULT_TASK: AtomicUsize = AtomicUsize::new(INVALID_TASK_ID);
-static WAKE_TASKS: [AtomicUsize; MAX_CHANNELS_USED] = [DEFAULT_TASK; MAX_CHANNELS_USED];
+impl<P> Drop for InputChannel<P> {
+    fn drop(&mut self) {
+        // SAFETY:
+        // Only this channel's bits/registers are touched, and nothing else
+        // uses this channel until it has been freed.
+        let gpiote = unsafe { &*microbit::pac::GPIOTE::ptr() };
+        gpiote
+            .intenclr
+            .write(|w| unsafe { w.bits(1 << self.channel_id) });
+        gpiote.config[self.channel_id].reset();
+        gpiote.events_in[self.channel_id].write(|w| w);
+        WAKE_TASKS[self.channel_id].store(INVALID_TASK_ID, Ordering::Relaxed);
+        QUEUE_EVENTS[self.channel_id].store(false, Ordering::Relaxed);
+        free_channel(self.channel_id);
+    }
+}
+
+/// Marks a wake slot with no task waiting on it. Shared with `sense`.
+pub(crate) const INVALID_TASK_ID: usize = 0xFFFF_FFFF;
+static WAKE_TASKS: [AtomicUsize; NUM_CHANNELS] =
+    [const { AtomicUsize::new(INVALID_TASK_ID) }; NUM_CHANNELS];
+/// Set by the interrupt handler on every IN event, for the edge-based waits.
+static EDGE_SEEN: [AtomicBool; NUM_CHANNELS] = [const { AtomicBool::new(false) }; NUM_CHANNELS];
+/// Set while a channel's owner is reading its edges through `next_event`.
+static QUEUE_EVENTS: [AtomicBool; NUM_CHANNELS] = [const { AtomicBool::new(false) }; NUM_CHANNELS];
+/// Room for a bit of switch bounce: if a queue is full, new edges get dropped.
+const EVENT_QUEUE_LEN: usize = 8;
+static EVENTS: [SyncChannel<InputEvent, EVENT_QUEUE_LEN>; NUM_CHANNELS] =
+    [const { SyncChannel::new() }; NUM_CHANNELS];
+
+/// Reads the current level of the pin hooked up to a GPIOTE channel.
+fn channel_level(gpiote: &microbit::pac::gpiote::RegisterBlock, channel: usize) -> PinState {
+    let config = gpiote.config[channel].read();
+    let pin = config.psel().bits();
+    // SAFETY:
+    // Read-only access to the IN register.
+    let port_in = unsafe {
+        if config.port().bit_is_set() {
+            (*P1::ptr()).in_.read().bits()
+        } else {
+            (*P0::ptr()).in_.read().bits()
+        }
+    };
+    PinState::from(port_in & (1 << pin) != 0)
+}
 
 #[interrupt]
 fn GPIOTE() {
     // SAFETY:
-    // Use limited to `events_in` register, which is not accessed elsewhere.
+    // Use limited to the `events_in` & `events_port` registers, which are not
+    // accessed elsewhere, plus reads of the `config` registers.
     let gpiote = unsafe { &*microbit::pac::GPIOTE::ptr() };
+    // Channels without the interrupt enabled aren't ours to handle (their
+    // events are used through PPI instead)
+    let enabled = gpiote.intenset.read().bits();
     for (channel, task) in WAKE_TASKS.iter().enumerate() {
+        if enabled & (1 << channel) == 0 {
+            continue;
+        }
         if gpiote.events_in[channel].read().bits() != 0 {
             gpiote.events_in[channel].write(|w| w);
+            EDGE_SEEN[channel].store(true, Ordering::Relaxed);
+            let event = InputEvent {
+                level: channel_level(gpiote, channel),
+                at: Ticker::now(),
+            };
+            // Anyone waiting on the queue gets woken through their waker
+            if QUEUE_EVENTS[channel].load(Ordering::Relaxed) {
+                EVENTS[channel].try_send(event).ok();
+            }
             // Swap in the INVALID_TASK_ID to prevent the task-ready queue from
             // getting filled up during debounce.
             let task_id = task.swap(INVALID_TASK_ID, Ordering::Relaxed);
@@ -74,6 +379,10 @@ fn GPIOTE() {
             }
         }
     }
+    if gpiote.events_port.read().bits() != 0 {
+        gpiote.events_port.write(|w| w);
+        sense::on_port_event();
+    }
     // Dummy read to ensure event flags clear
     // (see nRF52833 Product Specification section 6.1.8)
     let _ = gpiote.events_in[0].read().bits();
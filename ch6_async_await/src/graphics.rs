@@ -0,0 +1,136 @@
+use core::convert::Infallible;
+
+use embedded_graphics_core::{
+    pixelcolor::{Gray4, GrayColor},
+    prelude::{DrawTarget, OriginDimensions, Pixel, Size},
+};
+use microbit::gpio::{NUM_COLS, NUM_ROWS};
+
+use crate::display::{Frame, MAX_BRIGHTNESS};
+
+/// A frame to draw into with `embedded-graphics`, then put up on the `Display`
+/// with `display.show(buffer.frame())`.
+///
+/// Doesn't touch any hardware, so drawing code can be checked on the host too.
+/// `Gray4` goes from 0 to 15, which gets squeezed down to the display's
+/// brightness levels: `Gray4::WHITE` is `MAX_BRIGHTNESS`.
+pub struct FrameBuffer {
+    frame: Frame,
+}
+
+impl FrameBuffer {
+    pub const fn new() -> Self {
+        Self {
+            frame: [[0; NUM_COLS]; NUM_ROWS],
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Frame> for FrameBuffer {
+    fn from(frame: Frame) -> Self {
+        Self { frame }
+    }
+}
+
+fn to_level(color: Gray4) -> u8 {
+    // Rounded to the nearest level
+    ((color.luma() as u16 * MAX_BRIGHTNESS as u16 + 7) / 15) as u8
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = Gray4;
+    type Error = Infallible;
+
+    /// Anything outside the 5x5 area gets clipped.
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(col), Ok(row)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if row < NUM_ROWS && col < NUM_COLS {
+                self.frame[row][col] = to_level(color);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.frame = [[to_level(color); NUM_COLS]; NUM_ROWS];
+        Ok(())
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(NUM_COLS as u32, NUM_ROWS as u32)
+    }
+}
+
+/// Draws real `embedded-graphics` primitives into a `FrameBuffer` on the host:
+/// `cargo test --target x86_64-unknown-linux-gnu` (or your host's triple)
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        pixelcolor::Gray4,
+        prelude::*,
+        primitives::{Line, PrimitiveStyle, Rectangle},
+    };
+
+    use super::*;
+
+    #[test]
+    fn line_lights_up_its_pixels() {
+        let mut buffer = FrameBuffer::new();
+        Line::new(Point::new(0, 0), Point::new(4, 4))
+            .into_styled(PrimitiveStyle::with_stroke(Gray4::WHITE, 1))
+            .draw(&mut buffer)
+            .unwrap();
+        let mut expected = [[0; NUM_COLS]; NUM_ROWS];
+        for (i, row) in expected.iter_mut().enumerate() {
+            row[i] = MAX_BRIGHTNESS;
+        }
+        assert_eq!(buffer.frame(), expected);
+    }
+
+    #[test]
+    fn anything_off_the_edge_is_clipped() {
+        let mut buffer = FrameBuffer::new();
+        Rectangle::new(Point::new(3, -2), Size::new(4, 4))
+            .into_styled(PrimitiveStyle::with_fill(Gray4::WHITE))
+            .draw(&mut buffer)
+            .unwrap();
+        let m = MAX_BRIGHTNESS;
+        assert_eq!(
+            buffer.frame(),
+            [
+                [0, 0, 0, m, m],
+                [0, 0, 0, m, m],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn gray_levels_are_rounded_to_the_nearest_brightness() {
+        let mut buffer = FrameBuffer::new();
+        buffer.clear(Gray4::new(8)).unwrap();
+        assert_eq!(buffer.frame(), [[5; NUM_COLS]; NUM_ROWS]);
+        buffer.clear(Gray4::BLACK).unwrap();
+        assert_eq!(buffer.frame(), [[0; NUM_COLS]; NUM_ROWS]);
+    }
+}
@@ -1,43 +1,54 @@
-use embedded_hal::digital::{OutputPin, StatefulOutputPin};
-use microbit::{
-    gpio::NUM_COLS,
-    hal::gpio::{Output, Pin, PushPull},
-};
+use microbit::gpio::NUM_COLS;
 use rtt_target::rprintln;
+use zero_to_async::{
+    animation::Transition,
+    button::ButtonDirection,
+    display::{Display, MAX_BRIGHTNESS},
+    time::TickDuration,
+};
 
-use crate::button::ButtonDirection;
+/// How long the LED takes to fade in or out.
+const FADE_TIME: TickDuration = TickDuration::millis(180);
+/// How long the LED takes to move over to a new column.
+const SHIFT_TIME: TickDuration = TickDuration::millis(60);
 
-pub struct LedRow {
-    col: [Pin<Output<PushPull>>; NUM_COLS],
+/// A single blinking LED on the top row of the `Display`.
+pub struct LedRow<'a> {
+    display: &'a Display,
     active_col: usize,
 }
 
-impl LedRow {
-    pub fn new(
-        col: [Pin<Output<PushPull>>; NUM_COLS],
-    ) -> Self {
+impl<'a> LedRow<'a> {
+    pub fn new(display: &'a Display) -> Self {
         Self {
-            col,
+            display,
             active_col: 0,
         }
     }
 
-    pub fn shift(&mut self, direction: ButtonDirection) {
+    /// Cross-fades over to the next LED, which picks up wherever the old one
+    /// was in its blink.
+    pub async fn shift(&mut self, direction: ButtonDirection) {
         rprintln!("Button press detected..");
-        // switch off current/old LED
-        self.col[self.active_col].set_high().ok();
+        let mut frame = self.display.frame();
+        let level = frame[0][self.active_col];
+        frame[0][self.active_col] = 0;
         self.active_col = match direction {
             ButtonDirection::Left => match self.active_col {
                 0 => NUM_COLS - 1,
                 _ => self.active_col - 1,
-            }
+            },
             ButtonDirection::Right => (self.active_col + 1) % NUM_COLS,
+            ButtonDirection::Both => 0,
         };
-        // switch off new LED: moving to Toggle will then switch it on
-        self.col[self.active_col].set_high().ok();
+        frame[0][self.active_col] = level;
+        self.display
+            .transition(frame, Transition::Fade, SHIFT_TIME)
+            .await;
     }
 
-    pub fn toggle(&mut self) {
+    /// Fades the LED in if it's off, or out if it's on.
+    pub async fn toggle(&mut self) {
         rprintln!("Blinking LED {}", self.active_col);
         #[cfg(feature = "trigger-overflow")]
         {
@@ -49,6 +60,11 @@ impl LedRow {
                 time.duration_since_epoch().to_millis(),
             );
         }
-        self.col[self.active_col].toggle().ok();
+        let mut frame = self.display.frame();
+        let level = &mut frame[0][self.active_col];
+        *level = if *level == 0 { MAX_BRIGHTNESS } else { 0 };
+        self.display
+            .transition(frame, Transition::Fade, FADE_TIME)
+            .await;
     }
 }
@@ -0,0 +1,26 @@
+//! The async runtime and drivers, kept in a library so that `main` only pulls
+//! in what it uses, and so the pure logic can be unit tested on the host, with
+//! std: `cargo test --target x86_64-unknown-linux-gnu` (or your host's triple).
+#![cfg_attr(not(test), no_std)]
+
+pub mod animation;
+pub mod button;
+pub mod channel;
+pub mod debounce;
+pub mod display;
+pub mod event_group;
+pub mod executor;
+pub mod font;
+pub mod gpiote;
+pub mod graphics;
+pub mod mutex;
+pub mod oneshot;
+pub mod pipe;
+pub mod pubsub;
+pub mod semaphore;
+pub mod sense;
+pub mod signal;
+pub mod time;
+pub mod touch;
+pub mod waker;
+pub mod watch;
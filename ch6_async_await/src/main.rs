@@ -1,91 +1,88 @@
 #![no_std]
 #![no_main]
 
-mod button;
-mod channel;
-mod executor;
-mod gpiote;
 mod led;
-mod time;
 
 use core::pin::pin;
 
-use button::ButtonDirection;
-use channel::{Channel, Receiver, Sender};
-use cortex_m_rt::entry;
-use embedded_hal::digital::{OutputPin, PinState};
 use fugit::ExtU64;
 use futures::{select_biased, FutureExt};
-use gpiote::InputChannel;
 use led::LedRow;
-use microbit::{
-    gpio::NUM_COLS,
-    hal::{
-        gpio::{Floating, Input, Output, Pin, PushPull},
-        gpiote::Gpiote,
-    },
-    Board,
-};
+use microbit::{hal::gpiote::Gpiote, Board};
 use panic_rtt_target as _;
 use rtt_target::rtt_init_print;
-use time::Ticker;
+use zero_to_async::{
+    button::{Button, ButtonConfig, ButtonDirection, ButtonPair},
+    channel::{Channel, Receiver, Sender},
+    display::Display,
+    executor,
+    gpiote::{GpioteInput, InputChannel},
+    time::{self, Ticker},
+};
 
-#[entry]
+/// Enough room to keep up with some quick left/right presses.
+const BUTTON_EVENTS: usize = 4;
+
+#[cortex_m_rt::entry]
 fn main() -> ! {
     rtt_init_print!();
     let mut board = Board::take().unwrap();
     Ticker::init(board.RTC0, &mut board.NVIC);
     let gpiote = Gpiote::new(board.GPIOTE);
-    let (col, mut row) = board.display_pins.degrade();
-    row[0].set_high().ok();
-    let button_l = board.buttons.button_a.degrade();
-    let button_r = board.buttons.button_b.degrade();
+    let (col, row) = board.display_pins.degrade();
+    let display = Display::new(col, row, board.TIMER2);
+    let button_l = board.buttons.button_a;
+    let button_r = board.buttons.button_b;
 
-    let channel: Channel<ButtonDirection> = Channel::new();
-    let led_task = pin!(led_task(col, channel.get_receiver()));
-    let button_l_task = pin!(button_task(
+    let channel: Channel<ButtonDirection, BUTTON_EVENTS> = Channel::new();
+    let led_task = pin!(led_task(&display, channel.get_receiver()));
+    let button_task = pin!(button_task(
         button_l,
-        ButtonDirection::Left,
-        channel.get_sender(),
-        &gpiote
-    ));
-    let button_r_task = pin!(button_task(
         button_r,
-        ButtonDirection::Right,
         channel.get_sender(),
         &gpiote
     ));
 
-    executor::run_tasks(&mut [led_task, button_l_task, button_r_task]);
+    executor::run_tasks(&mut [led_task, button_task]);
 }
 
-async fn led_task(
-    col: [Pin<Output<PushPull>>; NUM_COLS],
-    mut receiver: Receiver<'_, ButtonDirection>,
-) {
-    let mut blinker = LedRow::new(col);
+async fn led_task(display: &Display, receiver: Receiver<'_, ButtonDirection, BUTTON_EVENTS>) {
+    let mut blinker = LedRow::new(display);
     loop {
-        blinker.toggle();
+        blinker.toggle().await;
         select_biased! {
-            direction = receiver.receive().fuse() => {
-                blinker.shift(direction);
-            }
-            _ = time::delay(500.millis()).fuse() => {}
+            direction = receiver.receive().fuse() => match direction {
+                Some(direction) => blinker.shift(direction).await,
+                // No buttons left to listen to
+                None => return,
+            },
+            _ = time::delay_with_slack(500.millis(), 20.millis()).fuse() => {}
         }
     }
 }
 
+/// Both buttons are handled by one task, so it can tell when they're pressed
+/// together. Holding a button down keeps the LED moving.
 async fn button_task(
-    pin: Pin<Input<Floating>>,
-    direction: ButtonDirection,
-    sender: Sender<'_, ButtonDirection>,
+    pin_l: impl GpioteInput,
+    pin_r: impl GpioteInput,
+    sender: Sender<'_, ButtonDirection, BUTTON_EVENTS>,
     gpiote: &Gpiote,
 ) {
-    let mut input = InputChannel::new(pin, gpiote);
+    let button_l = Button::new(
+        InputChannel::new(pin_l, gpiote).unwrap(),
+        ButtonConfig::default(),
+    );
+    let button_r = Button::new(
+        InputChannel::new(pin_r, gpiote).unwrap(),
+        ButtonConfig::default(),
+    );
+    let mut buttons = ButtonPair::new(button_l, button_r, 50.millis());
     loop {
-        input.wait_for(PinState::Low).await;
-        sender.send(direction);
-        time::delay(100.millis()).await;
-        input.wait_for(PinState::High).await;
+        let direction = buttons.next_direction().await;
+        if sender.send(direction).await.is_err() {
+            // Nobody is listening anymore
+            return;
+        }
     }
 }
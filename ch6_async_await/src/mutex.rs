@@ -0,0 +1,169 @@
+use core::{
+    cell::{RefCell, UnsafeCell},
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use heapless::Deque;
+
+/// Most tasks that can be queued up waiting for the lock.
+const MAX_WAITING: usize = 4;
+
+struct MutexState {
+    locked: bool,
+    /// Waiting `lock()` calls by ticket number, first come first served.
+    waiting: Deque<(u32, Waker), MAX_WAITING>,
+    next_ticket: u32,
+}
+
+impl MutexState {
+    /// Let the task at the front of the queue know it's their turn.
+    fn wake_next(&self) {
+        if let Some((_, waker)) = self.waiting.front() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+/// An async mutex, for sharing something like a driver between tasks.
+///
+/// Unlike `critical_section::Mutex`, waiting for the lock suspends the task
+/// rather than blocking interrupts, so the guard can be held across `.await`.
+/// Tasks get the lock in the order they asked for it.
+pub struct Mutex<T> {
+    state: RefCell<MutexState>,
+    value: UnsafeCell<T>,
+}
+
+impl<T> Mutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            state: RefCell::new(MutexState {
+                locked: false,
+                waiting: Deque::new(),
+                next_ticket: 0,
+            }),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn lock(&self) -> Lock<'_, T> {
+        Lock {
+            mutex: self,
+            ticket: None,
+        }
+    }
+
+    /// Doesn't jump the queue: fails if anybody is already waiting.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let mut state = self.state.borrow_mut();
+        if state.locked || !state.waiting.is_empty() {
+            return None;
+        }
+        state.locked = true;
+        Some(MutexGuard { mutex: self })
+    }
+}
+
+/// Future returned by `Mutex::lock`. If it gets dropped while waiting (e.g. in
+/// a `select_biased!` that went the other way), it gives up its place in line.
+pub struct Lock<'a, T> {
+    mutex: &'a Mutex<T>,
+    ticket: Option<u32>,
+}
+
+impl<'a, T> Future for Lock<'a, T> {
+    type Output = MutexGuard<'a, T>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.mutex.state.borrow_mut();
+        match self.ticket {
+            None => {
+                if !state.locked && state.waiting.is_empty() {
+                    state.locked = true;
+                    return Poll::Ready(MutexGuard { mutex: self.mutex });
+                }
+                let ticket = state.next_ticket;
+                state.next_ticket = ticket.wrapping_add(1);
+                if state
+                    .waiting
+                    .push_back((ticket, cx.waker().clone()))
+                    .is_err()
+                {
+                    panic!("Too many tasks waiting on Mutex!");
+                }
+                drop(state);
+                self.ticket = Some(ticket);
+                Poll::Pending
+            }
+            Some(ticket) => {
+                let is_next = matches!(state.waiting.front(), Some((next, _)) if *next == ticket);
+                if !state.locked && is_next {
+                    state.waiting.pop_front();
+                    state.locked = true;
+                    drop(state);
+                    self.ticket = None;
+                    return Poll::Ready(MutexGuard { mutex: self.mutex });
+                }
+                // Keep the waker up to date, in case we've moved tasks
+                if let Some((_, waker)) = state.waiting.iter_mut().find(|(t, _)| *t == ticket) {
+                    if !waker.will_wake(cx.waker()) {
+                        *waker = cx.waker().clone();
+                    }
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Lock<'_, T> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+        let mut state = self.mutex.state.borrow_mut();
+        let was_next = matches!(state.waiting.front(), Some((next, _)) if *next == ticket);
+        for _ in 0..state.waiting.len() {
+            if let Some(entry) = state.waiting.pop_front() {
+                if entry.0 != ticket {
+                    state.waiting.push_back(entry).ok();
+                }
+            }
+        }
+        // If we were about to get the lock, pass that on to the next in line
+        if was_next && !state.locked {
+            state.wake_next();
+        }
+    }
+}
+
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY:
+        // Only one `MutexGuard` can exist at a time for a given `Mutex`.
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY:
+        // Only one `MutexGuard` can exist at a time for a given `Mutex`.
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.mutex.state.borrow_mut();
+        state.locked = false;
+        state.wake_next();
+    }
+}
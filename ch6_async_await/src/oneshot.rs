@@ -0,0 +1,162 @@
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use critical_section::Mutex;
+
+/// The `Sender` was dropped without sending anything.
+#[derive(Debug)]
+pub struct Canceled;
+
+struct SlotState<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    /// Number of `Sender`/`Receiver` handles still around: the slot can only
+    /// be reused once both are gone.
+    handles: u8,
+    sender_done: bool,
+    receiver_dropped: bool,
+}
+
+/// Storage for a oneshot channel: there's no heap, so the caller provides the
+/// place where the value will be kept. It can be a `static` or live on the
+/// stack, and can be reused once both ends of the previous channel are gone.
+pub struct Slot<T> {
+    state: Mutex<RefCell<SlotState<T>>>,
+}
+
+impl<T> Slot<T> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(SlotState {
+                value: None,
+                waker: None,
+                handles: 0,
+                sender_done: false,
+                receiver_dropped: false,
+            })),
+        }
+    }
+}
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates a channel for sending exactly one value, e.g. the reply to a
+/// request sent to another task. Panics if `slot` is still in use.
+pub fn channel<T>(slot: &Slot<T>) -> (Sender<'_, T>, Receiver<'_, T>) {
+    critical_section::with(|cs| {
+        let mut state = slot.state.borrow_ref_mut(cs);
+        if state.handles != 0 {
+            panic!("Oneshot slot already in use!");
+        }
+        *state = SlotState {
+            value: None,
+            waker: None,
+            handles: 2,
+            sender_done: false,
+            receiver_dropped: false,
+        };
+    });
+    (Sender { slot }, Receiver { slot })
+}
+
+fn release<T>(state: &mut SlotState<T>) {
+    state.handles -= 1;
+    if state.handles == 0 {
+        state.value = None;
+        state.waker = None;
+    }
+}
+
+pub struct Sender<'a, T> {
+    slot: &'a Slot<T>,
+}
+
+impl<T> Sender<'_, T> {
+    /// Hands the value back if the `Receiver` has already been dropped.
+    /// Safe to call from interrupt context.
+    pub fn send(self, value: T) -> Result<(), T> {
+        critical_section::with(|cs| {
+            let mut state = self.slot.state.borrow_ref_mut(cs);
+            if state.receiver_dropped {
+                return Err(value);
+            }
+            state.value = Some(value);
+            Ok(())
+        })
+        // Dropping `self` takes care of waking the receiver
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        critical_section::with(|cs| self.slot.state.borrow_ref(cs).receiver_dropped)
+    }
+}
+
+impl<T> Drop for Sender<'_, T> {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            let mut state = self.slot.state.borrow_ref_mut(cs);
+            state.sender_done = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            release(&mut state);
+        });
+    }
+}
+
+/// Await it to get the value, or `Canceled` if the `Sender` went away first.
+pub struct Receiver<'a, T> {
+    slot: &'a Slot<T>,
+}
+
+impl<T> Receiver<'_, T> {
+    /// `Ok(None)` means nothing has been sent yet.
+    pub fn try_receive(&mut self) -> Result<Option<T>, Canceled> {
+        critical_section::with(|cs| {
+            let mut state = self.slot.state.borrow_ref_mut(cs);
+            match state.value.take() {
+                Some(value) => Ok(Some(value)),
+                None if state.sender_done => Err(Canceled),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+impl<T> Future for Receiver<'_, T> {
+    type Output = Result<T, Canceled>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        critical_section::with(|cs| {
+            let mut state = self.slot.state.borrow_ref_mut(cs);
+            match state.value.take() {
+                Some(value) => Poll::Ready(Ok(value)),
+                None if state.sender_done => Poll::Ready(Err(Canceled)),
+                None => {
+                    match &state.waker {
+                        Some(waker) if waker.will_wake(cx.waker()) => {}
+                        _ => state.waker = Some(cx.waker().clone()),
+                    }
+                    Poll::Pending
+                }
+            }
+        })
+    }
+}
+
+impl<T> Drop for Receiver<'_, T> {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            let mut state = self.slot.state.borrow_ref_mut(cs);
+            state.receiver_dropped = true;
+            release(&mut state);
+        });
+    }
+}
@@ -0,0 +1,255 @@
+use core::{
+    cell::RefCell,
+    convert::Infallible,
+    future::poll_fn,
+    task::{Poll, Waker},
+};
+
+use embedded_io_async::{Error, ErrorKind, ErrorType, Read, Write};
+use heapless::Deque;
+
+/// The reader has been dropped, so nothing written would ever be read.
+#[derive(Debug)]
+pub struct BrokenPipe;
+
+impl Error for BrokenPipe {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::BrokenPipe
+    }
+}
+
+struct PipeState<const N: usize> {
+    bytes: Deque<u8, N>,
+    reader_waker: Option<Waker>,
+    writer_waker: Option<Waker>,
+    has_reader: bool,
+    has_writer: bool,
+    /// Only set once a reader has come and gone: writes before the first
+    /// reader is taken just wait in the buffer.
+    reader_dropped: bool,
+}
+
+/// A single-producer single-consumer byte stream with room for `N` bytes in
+/// flight. Meant for streaming data (e.g. from a UART/RTT reader task into a
+/// parser task) without a channel message per byte.
+///
+/// Dropping the writer is the end of the stream: once the reader has drained
+/// what's left, `read` returns 0. Dropping the reader makes writes fail, until
+/// a new reader is taken (writing before the first reader is taken is fine).
+/// A new writer or reader can be taken after the old one has been dropped.
+pub struct Pipe<const N: usize> {
+    state: RefCell<PipeState<N>>,
+}
+
+impl<const N: usize> Pipe<N> {
+    pub fn new() -> Self {
+        Self {
+            state: RefCell::new(PipeState {
+                bytes: Deque::new(),
+                reader_waker: None,
+                writer_waker: None,
+                has_reader: false,
+                has_writer: false,
+                reader_dropped: false,
+            }),
+        }
+    }
+
+    /// Panics if there's already a writer.
+    pub fn get_writer(&self) -> Writer<'_, N> {
+        let mut state = self.state.borrow_mut();
+        if state.has_writer {
+            panic!("Pipe already has a writer!");
+        }
+        state.has_writer = true;
+        Writer { pipe: self }
+    }
+
+    /// Panics if there's already a reader.
+    pub fn get_reader(&self) -> Reader<'_, N> {
+        let mut state = self.state.borrow_mut();
+        if state.has_reader {
+            panic!("Pipe already has a reader!");
+        }
+        state.has_reader = true;
+        state.reader_dropped = false;
+        Reader { pipe: self }
+    }
+}
+
+impl<const N: usize> Default for Pipe<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn update_waker(slot: &mut Option<Waker>, waker: &Waker) {
+    match slot {
+        Some(current) if current.will_wake(waker) => {}
+        _ => *slot = Some(waker.clone()),
+    }
+}
+
+fn wake(slot: &mut Option<Waker>) {
+    if let Some(waker) = slot.take() {
+        waker.wake();
+    }
+}
+
+pub struct Writer<'a, const N: usize> {
+    pipe: &'a Pipe<N>,
+}
+
+impl<const N: usize> Writer<'_, N> {
+    /// Writes as much of `buf` as will fit, waiting if the pipe is full.
+    /// Returns the number of bytes written, which is only 0 if `buf` is empty.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, BrokenPipe> {
+        poll_fn(|cx| match self.try_write(buf) {
+            Ok(0) if !buf.is_empty() => {
+                update_waker(&mut self.pipe.state.borrow_mut().writer_waker, cx.waker());
+                Poll::Pending
+            }
+            result => Poll::Ready(result),
+        })
+        .await
+    }
+
+    pub fn try_write(&mut self, buf: &[u8]) -> Result<usize, BrokenPipe> {
+        let mut state = self.pipe.state.borrow_mut();
+        if state.reader_dropped {
+            return Err(BrokenPipe);
+        }
+        let mut written = 0;
+        for byte in buf {
+            if state.bytes.push_back(*byte).is_err() {
+                break;
+            }
+            written += 1;
+        }
+        if written > 0 {
+            wake(&mut state.reader_waker);
+        }
+        Ok(written)
+    }
+}
+
+impl<const N: usize> Drop for Writer<'_, N> {
+    fn drop(&mut self) {
+        let mut state = self.pipe.state.borrow_mut();
+        state.has_writer = false;
+        // Let the reader find out that the stream has ended
+        wake(&mut state.reader_waker);
+    }
+}
+
+impl<const N: usize> ErrorType for Writer<'_, N> {
+    type Error = BrokenPipe;
+}
+
+impl<const N: usize> Write for Writer<'_, N> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Writer::write(self, buf).await
+    }
+}
+
+pub struct Reader<'a, const N: usize> {
+    pipe: &'a Pipe<N>,
+}
+
+impl<const N: usize> Reader<'_, N> {
+    /// Waits for at least one byte, then reads as many as are available and
+    /// fit in `buf`. Returns 0 if `buf` is empty, or at the end of the stream:
+    /// the writer is gone and everything it wrote has been read.
+    pub async fn read(&mut self, buf: &mut [u8]) -> usize {
+        poll_fn(|cx| {
+            let read = self.try_read(buf);
+            let mut state = self.pipe.state.borrow_mut();
+            if read == 0 && !buf.is_empty() && state.has_writer {
+                update_waker(&mut state.reader_waker, cx.waker());
+                Poll::Pending
+            } else {
+                Poll::Ready(read)
+            }
+        })
+        .await
+    }
+
+    pub fn try_read(&mut self, buf: &mut [u8]) -> usize {
+        let mut state = self.pipe.state.borrow_mut();
+        let mut read = 0;
+        for slot in buf.iter_mut() {
+            match state.bytes.pop_front() {
+                Some(byte) => *slot = byte,
+                None => break,
+            }
+            read += 1;
+        }
+        if read > 0 {
+            wake(&mut state.writer_waker);
+        }
+        read
+    }
+}
+
+impl<const N: usize> Drop for Reader<'_, N> {
+    fn drop(&mut self) {
+        let mut state = self.pipe.state.borrow_mut();
+        state.has_reader = false;
+        state.reader_dropped = true;
+        // Nobody is going to read what's left, and a writer waiting for space
+        // would never get any
+        state.bytes.clear();
+        wake(&mut state.writer_waker);
+    }
+}
+
+impl<const N: usize> ErrorType for Reader<'_, N> {
+    type Error = Infallible;
+}
+
+impl<const N: usize> Read for Reader<'_, N> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(Reader::read(self, buf).await)
+    }
+}
+
+/// These run on the host rather than the micro:bit:
+/// `cargo test --target x86_64-unknown-linux-gnu` (or your host's triple)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writing_before_the_reader_is_taken_is_fine() {
+        let pipe: Pipe<4> = Pipe::new();
+        let mut writer = pipe.get_writer();
+        assert_eq!(writer.try_write(b"hi").unwrap(), 2);
+        let mut reader = pipe.get_reader();
+        let mut buf = [0; 4];
+        assert_eq!(reader.try_read(&mut buf), 2);
+        assert_eq!(&buf[..2], b"hi");
+    }
+
+    #[test]
+    fn writes_fail_once_the_reader_is_dropped() {
+        let pipe: Pipe<4> = Pipe::new();
+        let mut writer = pipe.get_writer();
+        drop(pipe.get_reader());
+        assert!(writer.try_write(b"hi").is_err());
+        // Until there's a new reader
+        let _reader = pipe.get_reader();
+        assert_eq!(writer.try_write(b"hi").unwrap(), 2);
+    }
+
+    #[test]
+    fn only_as_much_as_fits_gets_written() {
+        let pipe: Pipe<4> = Pipe::new();
+        let mut writer = pipe.get_writer();
+        let mut reader = pipe.get_reader();
+        assert_eq!(writer.try_write(b"hello").unwrap(), 4);
+        assert_eq!(writer.try_write(b"o").unwrap(), 0);
+        let mut buf = [0; 8];
+        assert_eq!(reader.try_read(&mut buf), 4);
+        assert_eq!(&buf[..4], b"hell");
+    }
+}
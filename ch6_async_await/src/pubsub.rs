@@ -0,0 +1,148 @@
+use core::{
+    cell::RefCell,
+    future::poll_fn,
+    task::{Context, Poll},
+};
+
+use heapless::Deque;
+
+use crate::waker::WakerSlots;
+
+/// A subscriber fell behind by this many messages, which were overwritten
+/// before it got to read them.
+#[derive(Debug)]
+pub struct Lagged(pub u64);
+
+struct PubSubState<T, const CAP: usize, const SUBS: usize> {
+    messages: Deque<T, CAP>,
+    /// Sequence number the next published message will get.
+    next_seq: u64,
+    subscribers: WakerSlots<SUBS>,
+}
+
+impl<T: Clone, const CAP: usize, const SUBS: usize> PubSubState<T, CAP, SUBS> {
+    /// Sequence number of the oldest message still in the buffer.
+    fn oldest_seq(&self) -> u64 {
+        self.next_seq - self.messages.len() as u64
+    }
+
+    fn publish(&mut self, item: T) {
+        // Publishing never waits on slow subscribers: the oldest message gets
+        // dropped instead, and anyone who hadn't read it yet will lag.
+        if self.messages.is_full() {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(item).ok();
+        self.next_seq += 1;
+        self.subscribers.wake_all();
+    }
+
+    fn try_next(&self, seq: &mut u64) -> Option<Result<T, Lagged>> {
+        let oldest = self.oldest_seq();
+        if *seq < oldest {
+            let missed = oldest - *seq;
+            *seq = oldest;
+            return Some(Err(Lagged(missed)));
+        }
+        let item = self.messages.iter().nth((*seq - oldest) as usize)?.clone();
+        *seq += 1;
+        Some(Ok(item))
+    }
+
+    fn poll_next(
+        &mut self,
+        cx: &mut Context<'_>,
+        slot: usize,
+        seq: &mut u64,
+    ) -> Poll<Result<T, Lagged>> {
+        match self.try_next(seq) {
+            Some(result) => Poll::Ready(result),
+            None => {
+                self.subscribers.register(slot, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A broadcast channel: every subscriber gets its own copy of each message.
+///
+/// The last `CAP` messages are kept around, and each of the (up to) `SUBS`
+/// subscribers has its own read cursor into them, along with its own waker.
+pub struct PubSub<T, const CAP: usize, const SUBS: usize> {
+    state: RefCell<PubSubState<T, CAP, SUBS>>,
+}
+
+impl<T: Clone, const CAP: usize, const SUBS: usize> PubSub<T, CAP, SUBS> {
+    pub fn new() -> Self {
+        Self {
+            state: RefCell::new(PubSubState {
+                messages: Deque::new(),
+                next_seq: 0,
+                subscribers: WakerSlots::new(),
+            }),
+        }
+    }
+
+    pub fn get_publisher(&self) -> Publisher<'_, T, CAP, SUBS> {
+        Publisher { pubsub: self }
+    }
+
+    /// New subscribers only see messages published from here on.
+    /// Returns `None` if all `SUBS` subscriber slots are in use.
+    pub fn get_subscriber(&self) -> Option<Subscriber<'_, T, CAP, SUBS>> {
+        let mut state = self.state.borrow_mut();
+        let slot = state.subscribers.claim()?;
+        Some(Subscriber {
+            pubsub: self,
+            slot,
+            next_seq: state.next_seq,
+        })
+    }
+}
+
+impl<T: Clone, const CAP: usize, const SUBS: usize> Default for PubSub<T, CAP, SUBS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Publisher<'a, T, const CAP: usize, const SUBS: usize> {
+    pubsub: &'a PubSub<T, CAP, SUBS>,
+}
+
+impl<T: Clone, const CAP: usize, const SUBS: usize> Publisher<'_, T, CAP, SUBS> {
+    pub fn publish(&self, item: T) {
+        self.pubsub.state.borrow_mut().publish(item);
+    }
+}
+
+pub struct Subscriber<'a, T, const CAP: usize, const SUBS: usize> {
+    pubsub: &'a PubSub<T, CAP, SUBS>,
+    slot: usize,
+    next_seq: u64,
+}
+
+impl<T: Clone, const CAP: usize, const SUBS: usize> Subscriber<'_, T, CAP, SUBS> {
+    /// After a `Lagged` error, the cursor has already been moved up to the
+    /// oldest message still available, so just keep reading.
+    pub async fn next_message(&mut self) -> Result<T, Lagged> {
+        poll_fn(|cx| {
+            self.pubsub
+                .state
+                .borrow_mut()
+                .poll_next(cx, self.slot, &mut self.next_seq)
+        })
+        .await
+    }
+
+    pub fn try_next_message(&mut self) -> Option<Result<T, Lagged>> {
+        self.pubsub.state.borrow().try_next(&mut self.next_seq)
+    }
+}
+
+impl<T, const CAP: usize, const SUBS: usize> Drop for Subscriber<'_, T, CAP, SUBS> {
+    fn drop(&mut self) {
+        self.pubsub.state.borrow_mut().subscribers.release(self.slot);
+    }
+}
@@ -0,0 +1,92 @@
+use core::{cell::RefCell, future::poll_fn, mem, task::Poll};
+
+use critical_section::Mutex;
+
+use crate::waker::WakerList;
+
+/// Most tasks that can be waiting on a permit at once.
+const MAX_WAITING: usize = 4;
+
+struct SemaphoreState {
+    permits: usize,
+    waiters: WakerList<MAX_WAITING>,
+}
+
+/// A counting semaphore, for limiting how many tasks can use something at once
+/// (GPIOTE channels, DMA buffers...).
+///
+/// Permits can be handed back from an interrupt handler with `release`, so it
+/// can also be used to count up events for a task to work through.
+pub struct Semaphore {
+    state: Mutex<RefCell<SemaphoreState>>,
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(SemaphoreState {
+                permits,
+                waiters: WakerList::new(),
+            })),
+        }
+    }
+
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                let mut state = self.state.borrow_ref_mut(cs);
+                if state.permits > 0 {
+                    state.permits -= 1;
+                    Poll::Ready(SemaphorePermit { semaphore: self })
+                } else {
+                    state.waiters.register(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            if state.permits > 0 {
+                state.permits -= 1;
+                Some(SemaphorePermit { semaphore: self })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Safe to call from interrupt context.
+    pub fn release(&self, permits: usize) {
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            state.permits += permits;
+            state.waiters.wake_all();
+        });
+    }
+
+    pub fn available(&self) -> usize {
+        critical_section::with(|cs| self.state.borrow_ref(cs).permits)
+    }
+}
+
+/// The permit goes back to the `Semaphore` when this is dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl SemaphorePermit<'_> {
+    /// Use up the permit for good, rather than handing it back on drop.
+    pub fn forget(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.release(1);
+    }
+}
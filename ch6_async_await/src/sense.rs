@@ -0,0 +1,148 @@
+use core::{
+    future::poll_fn,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::Poll,
+};
+
+use embedded_hal::digital::PinState;
+use microbit::{
+    hal::{
+        gpio::{Floating, Input, Pin},
+        gpiote::Gpiote,
+    },
+    pac::{p0, Interrupt, NVIC, P0, P1},
+};
+
+use crate::{
+    executor::{wake_task, ExtWaker},
+    gpiote::{ActiveLevel, GpioteInput, INVALID_TASK_ID},
+};
+
+const NUM_PORTS: usize = 2;
+const PINS_PER_PORT: usize = 32;
+
+/// An input that is waited on using the GPIO DETECT signal & the single GPIOTE
+/// PORT event, rather than a GPIOTE IN channel. There's no limit on how many
+/// of these there can be, and they don't draw any extra current while idle.
+///
+/// The trade-off is that every pin shares one event, so the interrupt handler
+/// has to check the LATCH registers to find out which pins changed.
+///
+/// Takes the same `GpioteInput` pins as an `InputChannel` (degraded or typed),
+/// so either backend can be used for a given input.
+pub struct SenseInput<P = Pin<Input<Floating>>> {
+    pin: P,
+    /// Port number in bit 5, pin number in bits 0-4: same as PSEL
+    pin_id: usize,
+    active_level: ActiveLevel,
+}
+
+impl<P: GpioteInput> SenseInput<P> {
+    /// Inputs start out active-low, like the micro:bit buttons.
+    pub fn new(pin: P, gpiote: &Gpiote) -> Self {
+        // Latched detect mode, so we can tell which pins triggered the event
+        for port in 0..NUM_PORTS {
+            port_regs(port)
+                .detectmode
+                .write(|w| w.detectmode().ldetect());
+        }
+        gpiote.port().enable_interrupt();
+        // SAFETY:
+        // We aren't using mask-based critical sections.
+        unsafe { NVIC::unmask(Interrupt::GPIOTE); }
+        let pin_id = (pin.port() as usize) * PINS_PER_PORT + pin.pin() as usize;
+        Self {
+            pin,
+            pin_id,
+            active_level: ActiveLevel::Low,
+        }
+    }
+
+    pub fn set_active_level(&mut self, active_level: ActiveLevel) {
+        self.active_level = active_level;
+    }
+
+    pub fn active_level(&self) -> ActiveLevel {
+        self.active_level
+    }
+
+    pub fn is_active(&mut self) -> bool {
+        PinState::from(self.pin.is_high().unwrap()) == self.active_level.active_state()
+    }
+
+    pub async fn wait_for_active(&mut self) {
+        self.wait_for(self.active_level.active_state()).await
+    }
+
+    pub async fn wait_for_inactive(&mut self) {
+        self.wait_for(self.active_level.inactive_state()).await
+    }
+
+    pub async fn wait_for(&mut self, ready_state: PinState) {
+        poll_fn(|cx| {
+            if ready_state == PinState::from(self.pin.is_high().unwrap()) {
+                Poll::Ready(())
+            } else {
+                WAKE_TASKS[self.pin_id].store(cx.waker().task_id(), Ordering::Relaxed);
+                // If the pin has already changed by now, DETECT is raised as
+                // soon as SENSE is set, so the wakeup won't be missed.
+                pin_cnf(self.pin_id).modify(|_, w| match ready_state {
+                    PinState::Low => w.sense().low(),
+                    PinState::High => w.sense().high(),
+                });
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<P> Drop for SenseInput<P> {
+    fn drop(&mut self) {
+        pin_cnf(self.pin_id).modify(|_, w| w.sense().disabled());
+        WAKE_TASKS[self.pin_id].store(INVALID_TASK_ID, Ordering::Relaxed);
+    }
+}
+
+fn port_regs(port: usize) -> &'static p0::RegisterBlock {
+    // SAFETY:
+    // Use limited to the DETECTMODE & LATCH registers, plus the PIN_CNF
+    // registers for pins owned by a `SenseInput`.
+    unsafe {
+        match port {
+            0 => &*P0::ptr(),
+            _ => &*P1::ptr(),
+        }
+    }
+}
+
+fn pin_cnf(pin_id: usize) -> &'static p0::PIN_CNF {
+    &port_regs(pin_id / PINS_PER_PORT).pin_cnf[pin_id % PINS_PER_PORT]
+}
+
+static WAKE_TASKS: [AtomicUsize; NUM_PORTS * PINS_PER_PORT] =
+    [const { AtomicUsize::new(INVALID_TASK_ID) }; NUM_PORTS * PINS_PER_PORT];
+
+/// Called from the `GPIOTE()` interrupt handler when the PORT event fires.
+pub fn on_port_event() {
+    for port in 0..NUM_PORTS {
+        let regs = port_regs(port);
+        let latch = regs.latch.read().bits();
+        for pin in 0..PINS_PER_PORT {
+            if latch & (1 << pin) == 0 {
+                continue;
+            }
+            // Turn SENSE off before clearing the latch, otherwise it would
+            // just get set again while the pin stays at the sensed level.
+            // `wait_for` turns it back on as needed.
+            let pin_id = port * PINS_PER_PORT + pin;
+            pin_cnf(pin_id).modify(|_, w| w.sense().disabled());
+            let task_id = WAKE_TASKS[pin_id].swap(INVALID_TASK_ID, Ordering::Relaxed);
+            if task_id != INVALID_TASK_ID {
+                wake_task(task_id);
+            }
+        }
+        // LATCH bits are cleared by writing '1' to them
+        regs.latch.write(|w| unsafe { w.bits(latch) });
+    }
+}
@@ -0,0 +1,81 @@
+use core::{
+    cell::RefCell,
+    future::poll_fn,
+    task::{Poll, Waker},
+};
+
+use critical_section::Mutex;
+
+struct SignalState<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Holds the latest value only: signalling again before the waiting task has
+/// picked it up just overwrites it. There's room for one waiting task.
+///
+/// The state lives behind a critical section, so a `Signal` can be a `static`
+/// that is signalled from an interrupt handler.
+pub struct Signal<T> {
+    state: Mutex<RefCell<SignalState<T>>>,
+}
+
+impl<T> Signal<T> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(SignalState {
+                value: None,
+                waker: None,
+            })),
+        }
+    }
+
+    pub fn signal(&self, value: T) {
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            state.value = Some(value);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+    }
+
+    pub fn reset(&self) {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).value = None);
+    }
+
+    pub fn is_signaled(&self) -> bool {
+        critical_section::with(|cs| self.state.borrow_ref(cs).value.is_some())
+    }
+
+    pub fn try_take(&self) -> Option<T> {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).value.take())
+    }
+
+    /// Only the most recent waiter gets woken: any task that was waiting
+    /// before it loses its place.
+    pub async fn wait(&self) -> T {
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                let mut state = self.state.borrow_ref_mut(cs);
+                match state.value.take() {
+                    Some(value) => Poll::Ready(value),
+                    None => {
+                        match &state.waker {
+                            Some(waker) if waker.will_wake(cx.waker()) => {}
+                            _ => state.waker = Some(cx.waker().clone()),
+                        }
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+}
+
+impl<T> Default for Signal<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -19,74 +19,137 @@ use microbit::{
 
 use crate::executor::{wake_task, ExtWaker};
 
-type TickInstant = Instant<u64, 1, 32768>;
-type TickDuration = Duration<u64, 1, 32768>;
+pub type TickInstant = Instant<u64, 1, 32768>;
+pub type TickDuration = Duration<u64, 1, 32768>;
 
 const MAX_DEADLINES: usize = 8;
-static WAKE_DEADLINES: Mutex<RefCell<BinaryHeap<(u64, usize), Min, MAX_DEADLINES>>> =
+/// Each entry is `(deadline, latest, task_id, timer_id)`: the task may be
+/// woken at any point between its deadline and the end of its slack window
+/// (`latest`). The timer's id lets it find its own entry again.
+type Deadline = (u64, u64, usize, u32);
+static WAKE_DEADLINES: Mutex<RefCell<BinaryHeap<Deadline, Min, MAX_DEADLINES>>> =
     Mutex::new(RefCell::new(BinaryHeap::new()));
+static NEXT_TIMER_ID: AtomicU32 = AtomicU32::new(0);
+
+/// A `BinaryHeap` can only give up its smallest entry, so the heap gets
+/// rebuilt without this timer's entry (if it has one).
+fn remove_deadline(deadlines: &mut BinaryHeap<Deadline, Min, MAX_DEADLINES>, timer_id: u32) {
+    if !deadlines.iter().any(|(_, _, _, id)| *id == timer_id) {
+        return;
+    }
+    let old = core::mem::replace(deadlines, BinaryHeap::new());
+    for entry in old.into_vec() {
+        if entry.3 != timer_id {
+            deadlines.push(entry).ok();
+        }
+    }
+}
 
 /// Deadlines can only be scheduled in a COMPARE register if they fall within
 /// the current overflow-cycle/epoch, and also are not too close to the current
 /// counter value. (see nRF52833 Product Specification section 6.20.7)
+///
+/// To save on wakeups, timers with overlapping slack windows get coalesced:
+/// COMPARE0 is set to the earliest window end, and when it fires every
+/// deadline that has been reached by then is woken together.
 fn schedule_wakeup(
-    mut rm_deadlines: RefMut<BinaryHeap<(u64, usize), Min, MAX_DEADLINES>>,
+    mut rm_deadlines: RefMut<BinaryHeap<Deadline, Min, MAX_DEADLINES>>,
     mut rm_rtc: RefMut<Option<Rtc<RTC0>>>,
 ) {
     let rtc = rm_rtc.as_mut().unwrap();
-    while let Some((deadline, task_id)) = rm_deadlines.peek() {
-        let ovf_count = (*deadline >> 24) as u32;
-        if ovf_count == TICKER.ovf_count.load(Ordering::Relaxed) {
-            let counter = (*deadline & 0xFF_FF_FF) as u32;
-            if counter > (rtc.get_counter() + 1) {
-                rtc.set_compare(RtcCompareReg::Compare0, counter).ok();
-                rtc.enable_event(RtcInterrupt::Compare0);
-            } else {
-                // Wake now if it's too close or already past,
-                // then try again with the next available deadline
-                wake_task(*task_id);
-                rm_deadlines.pop();
-                continue;
+    while let Some(wakeup) = rm_deadlines.iter().map(|(_, latest, _, _)| *latest).min() {
+        let ovf_count = TICKER.ovf_count.load(Ordering::Relaxed);
+        let now = (ovf_count as u64) << 24 | rtc.get_counter() as u64;
+        if wakeup > now + 1 {
+            set_compare(rtc, ovf_count, wakeup);
+            break;
+        }
+        // Wake everything that has been reached, then try again with what's
+        // left. Nothing gets woken early: a timer woken before its deadline
+        // would just have to register again.
+        let mut woken = false;
+        while let Some((deadline, _, task_id, _)) = rm_deadlines.peek() {
+            if *deadline > now {
+                break;
             }
+            wake_task(*task_id);
+            rm_deadlines.pop();
+            woken = true;
+        }
+        if !woken {
+            // Only deadlines at `now + 1` are left, which is too close for
+            // COMPARE0 to catch: wait for the tick after instead
+            set_compare(rtc, ovf_count, now + 2);
+            break;
         }
-        break;
     }
     if rm_deadlines.is_empty() {
         rtc.disable_event(RtcInterrupt::Compare0);
     }
 }
 
+/// A wakeup in the next epoch gets scheduled by the overflow interrupt instead.
+fn set_compare(rtc: &mut Rtc<RTC0>, ovf_count: u32, wakeup: u64) {
+    if (wakeup >> 24) as u32 == ovf_count {
+        rtc.set_compare(RtcCompareReg::Compare0, (wakeup & 0xFF_FF_FF) as u32)
+            .ok();
+        rtc.enable_event(RtcInterrupt::Compare0);
+    }
+}
+
 enum TimerState {
     Init,
     Wait,
 }
 
+/// A timer that gets dropped before it expires (e.g. in a `select_biased!` that
+/// went the other way) takes its deadline back out of the queue.
 pub struct Timer {
+    id: u32,
     end_time: TickInstant,
+    slack: TickDuration,
     state: TimerState,
 }
 
 impl Timer {
     pub fn new(duration: TickDuration) -> Self {
+        Self::with_slack(duration, TickDuration::from_ticks(0))
+    }
+
+    /// A timer that may expire up to `slack` later than requested, which lets
+    /// its wakeup be shared with any other timer whose window overlaps.
+    pub fn with_slack(duration: TickDuration, slack: TickDuration) -> Self {
         Self {
+            id: NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed),
             end_time: Ticker::now() + duration,
+            slack,
+            state: TimerState::Init,
+        }
+    }
+
+    /// A timer that expires at a fixed point in time, rather than after a
+    /// duration. If that's already in the past, it expires straight away.
+    pub fn at(end_time: TickInstant) -> Self {
+        Self {
+            id: NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed),
+            end_time,
+            slack: TickDuration::from_ticks(0),
             state: TimerState::Init,
         }
     }
 
     /// Registration places the deadline & its task_id onto a `BinaryHeap`, and
-    /// then will attempt to schedule it (via COMPARE0) if it's earlier than
-    /// the current deadline.
+    /// then reschedules COMPARE0, as the new slack window may close earlier
+    /// than the currently scheduled wakeup. Registering again replaces the
+    /// timer's previous entry, if it still has one.
     fn register(&self, task_id: usize) {
-        let new_deadline = self.end_time.ticks();
+        let deadline = self.end_time.ticks();
+        let latest = deadline + self.slack.ticks();
         critical_section::with(|cs| {
             let mut rm_deadlines = WAKE_DEADLINES.borrow_ref_mut(cs);
-            let is_earliest = if let Some((next_deadline, _)) = rm_deadlines.peek() {
-                new_deadline < *next_deadline
-            } else {
-                true
-            };
-            if rm_deadlines.push((new_deadline, task_id)).is_err() {
+            remove_deadline(&mut rm_deadlines, self.id);
+            let entry = (deadline, latest, task_id, self.id);
+            if rm_deadlines.push(entry).is_err() {
                 // Dropping a deadline in this system can be Very Bad:
                 //  - In the LED task, the LED will stop updating, but may come
                 //    back to life on a button press...
@@ -94,10 +157,7 @@ impl Timer {
                 // `panic` to raise awareness of the issue during development
                 panic!("Deadline dropped for task {}!", task_id);
             }
-            // schedule now if its the earliest
-            if is_earliest {
-                schedule_wakeup(rm_deadlines, TICKER.rtc.borrow_ref_mut(cs));
-            }
+            schedule_wakeup(rm_deadlines, TICKER.rtc.borrow_ref_mut(cs));
         });
     }
 }
@@ -115,6 +175,10 @@ impl Future for Timer {
                 if Ticker::now() >= self.end_time {
                     Poll::Ready(())
                 } else {
+                    // Polled early, maybe by a wakeup meant for something
+                    // else. If this timer's entry has already been used up,
+                    // nothing would wake us again: make sure there is one.
+                    self.register(cx.waker().task_id());
                     Poll::Pending
                 }
             }
@@ -122,10 +186,24 @@ impl Future for Timer {
     }
 }
 
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if let TimerState::Wait = self.state {
+            critical_section::with(|cs| {
+                remove_deadline(&mut WAKE_DEADLINES.borrow_ref_mut(cs), self.id);
+            });
+        }
+    }
+}
+
 pub async fn delay(duration: TickDuration) {
     Timer::new(duration).await;
 }
 
+pub async fn delay_with_slack(duration: TickDuration, slack: TickDuration) {
+    Timer::with_slack(duration, slack).await;
+}
+
 static TICKER: Ticker = Ticker {
     ovf_count: AtomicU32::new(0),
     rtc: Mutex::new(RefCell::new(None)),
@@ -0,0 +1,205 @@
+use fugit::ExtU64;
+use microbit::{
+    hal::gpio::{Floating, Input, Level, Pin},
+    pac::{GPIOTE, PPI, TIMER1},
+};
+
+use crate::{
+    gpiote::{alloc_channel, free_channel, ChannelsExhausted},
+    time::{self, TickDuration},
+};
+
+#[derive(Clone, Copy)]
+pub struct TouchConfig {
+    /// How often to measure the pad while waiting.
+    pub sample_period: TickDuration,
+    /// Charge time has to go this far (in %) above the baseline to count as
+    /// a touch...
+    pub touch_threshold: u32,
+    /// ...and then drop back below this (in %) to count as a release.
+    pub release_threshold: u32,
+    /// Give up on a measurement after this many TIMER ticks (16 MHz).
+    pub timeout: u32,
+}
+
+impl Default for TouchConfig {
+    fn default() -> Self {
+        Self {
+            sample_period: 20.millis(),
+            touch_threshold: 30,
+            release_threshold: 15,
+            timeout: 16_000,
+        }
+    }
+}
+
+/// Number of samples averaged to get the starting baseline.
+const CALIBRATION_SAMPLES: u32 = 8;
+/// Links the pad's GPIOTE event to TIMER1's capture task.
+const PPI_CHANNEL: usize = 0;
+
+/// Capacitive touch sensing, e.g. for the micro:bit v2 logo (P1_04).
+///
+/// The pad is discharged by driving it low, then released, and TIMER1 measures
+/// how long it takes to charge back up. The pin is left floating, so this
+/// relies on the board's external pull-up resistor on P1_04. A finger adds
+/// capacitance, which makes charging take longer.
+///
+/// The end of the measurement is caught in hardware: a GPIOTE channel sees the
+/// pin go high and triggers TIMER1's capture task through PPI. So interrupts
+/// only need to be held off for the couple of register writes at the start.
+///
+/// The untouched "baseline" charge time is measured on startup, then keeps
+/// tracking slow drift (temperature, humidity...) while the pad isn't touched.
+pub struct TouchPad {
+    /// Only `None` while a measurement is switching the pin's mode around
+    pin: Option<Pin<Input<Floating>>>,
+    timer: TIMER1,
+    ppi: PPI,
+    channel_id: usize,
+    config: TouchConfig,
+    baseline: u32,
+    touched: bool,
+}
+
+impl TouchPad {
+    /// Needs one of the GPIOTE channels, which it keeps until it's dropped.
+    pub fn new(
+        pin: Pin<Input<Floating>>,
+        timer: TIMER1,
+        ppi: PPI,
+        config: TouchConfig,
+    ) -> Result<Self, ChannelsExhausted> {
+        let channel_id = alloc_channel()?;
+        // Free-running 32-bit timer at the full 16 MHz
+        timer.mode.write(|w| w.mode().timer());
+        timer.bitmode.write(|w| w.bitmode()._32bit());
+        timer.prescaler.write(|w| unsafe { w.prescaler().bits(0) });
+        // Rising edge on the pad, but left disabled until a measurement
+        // starts: in Event mode GPIOTE takes the pin over as an input, which
+        // would get in the way of discharging it.
+        let psel = pin.psel_bits();
+        gpiote().config[channel_id].write(|w| {
+            // SAFETY:
+            // PSEL takes the 5-bit pin number, and PORT the port.
+            unsafe { w.psel().bits(psel as u8 & 0x1F) }
+                .port()
+                .bit(psel & 0x20 != 0)
+                .polarity()
+                .lo_to_hi()
+                .mode()
+                .disabled()
+        });
+        ppi.ch[PPI_CHANNEL]
+            .eep
+            .write(|w| unsafe { w.bits(&gpiote().events_in[channel_id] as *const _ as u32) });
+        ppi.ch[PPI_CHANNEL]
+            .tep
+            .write(|w| unsafe { w.bits(&timer.tasks_capture[0] as *const _ as u32) });
+        ppi.chenset.write(|w| unsafe { w.bits(1 << PPI_CHANNEL) });
+        let mut touch_pad = Self {
+            pin: Some(pin),
+            timer,
+            ppi,
+            channel_id,
+            config,
+            baseline: 0,
+            touched: false,
+        };
+        touch_pad.calibrate();
+        Ok(touch_pad)
+    }
+
+    /// Re-measure the baseline: the pad must not be touched while this runs.
+    pub fn calibrate(&mut self) {
+        let total: u32 = (0..CALIBRATION_SAMPLES).map(|_| self.measure()).sum();
+        self.baseline = total / CALIBRATION_SAMPLES;
+        self.touched = false;
+    }
+
+    pub fn is_touched(&self) -> bool {
+        self.touched
+    }
+
+    pub async fn wait_for_touch(&mut self) {
+        while !self.sample() {
+            time::delay(self.config.sample_period).await;
+        }
+    }
+
+    pub async fn wait_for_release(&mut self) {
+        while self.sample() {
+            time::delay(self.config.sample_period).await;
+        }
+    }
+
+    /// Takes a measurement and updates the touch state, with hysteresis
+    /// between the touch & release thresholds so it doesn't chatter.
+    fn sample(&mut self) -> bool {
+        let reading = self.measure();
+        let threshold = if self.touched {
+            self.config.release_threshold
+        } else {
+            self.config.touch_threshold
+        };
+        self.touched = reading > self.baseline + self.baseline * threshold / 100;
+        if !self.touched {
+            // Slowly follow the untouched reading: 1/8th of the way each time
+            self.baseline = (self.baseline * 7 + reading) / 8;
+        }
+        self.touched
+    }
+
+    /// Charge time in TIMER ticks, or `timeout` if the pad never went high.
+    fn measure(&mut self) -> u32 {
+        let pin = self.pin.take().unwrap();
+        let discharge = pin.into_push_pull_output(Level::Low);
+        // ~10µs at 64 MHz is plenty to empty the pad
+        cortex_m::asm::delay(640);
+        let config = &gpiote().config[self.channel_id];
+        let event = &gpiote().events_in[self.channel_id];
+        event.write(|w| w);
+        // An interrupt between starting the timer and letting go of the pin
+        // would throw the timing off, but the rest can be interrupted: the end
+        // gets captured by PPI.
+        let pin = critical_section::with(|_| {
+            self.timer.tasks_clear.write(|w| unsafe { w.bits(1) });
+            self.timer.tasks_start.write(|w| unsafe { w.bits(1) });
+            let pin = discharge.into_floating_input();
+            config.modify(|_, w| w.mode().event());
+            pin
+        });
+        let ticks = loop {
+            if event.read().bits() != 0 {
+                break self.timer.cc[0].read().bits();
+            }
+            self.timer.tasks_capture[1].write(|w| unsafe { w.bits(1) });
+            if self.timer.cc[1].read().bits() >= self.config.timeout {
+                break self.config.timeout;
+            }
+        };
+        self.timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+        config.modify(|_, w| w.mode().disabled());
+        event.write(|w| w);
+        self.pin = Some(pin);
+        ticks.min(self.config.timeout)
+    }
+}
+
+impl Drop for TouchPad {
+    fn drop(&mut self) {
+        self.ppi
+            .chenclr
+            .write(|w| unsafe { w.bits(1 << PPI_CHANNEL) });
+        gpiote().config[self.channel_id].reset();
+        gpiote().events_in[self.channel_id].write(|w| w);
+        free_channel(self.channel_id);
+    }
+}
+
+fn gpiote() -> &'static microbit::pac::gpiote::RegisterBlock {
+    // SAFETY:
+    // Only the CONFIG & EVENTS_IN registers of the channel allocated to the
+    // `TouchPad` are touched, and the interrupt handler skips that channel.
+    unsafe { &*GPIOTE::ptr() }
+}
@@ -0,0 +1,109 @@
+use core::task::Waker;
+
+use heapless::Vec;
+
+/// A fixed-size list of wakers, for when more than one task can be waiting on
+/// the same thing. Wakers are kept in the order they were registered, so the
+/// task that has been waiting the longest is also the first to be queued up
+/// by the executor.
+pub struct WakerList<const N: usize> {
+    wakers: Vec<Waker, N>,
+}
+
+impl<const N: usize> WakerList<N> {
+    pub const fn new() -> Self {
+        Self { wakers: Vec::new() }
+    }
+
+    /// Registering the same task twice is a no-op, so it's fine to call this
+    /// on every `Poll::Pending`.
+    pub fn register(&mut self, waker: &Waker) {
+        if self.wakers.iter().any(|w| w.will_wake(waker)) {
+            return;
+        }
+        if self.wakers.push(waker.clone()).is_err() {
+            // Same story as a dropped deadline: a task that doesn't get woken
+            // may never run again.
+            panic!("Waker list full!");
+        }
+    }
+
+    pub fn wake_all(&mut self) {
+        for waker in self.wakers.iter() {
+            waker.wake_by_ref();
+        }
+        self.wakers.clear();
+    }
+}
+
+impl<const N: usize> Default for WakerList<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum WakerSlot {
+    Free,
+    Taken(Option<Waker>),
+}
+
+/// Fixed waker slots, one per handle (e.g. each `Receiver`), for when every
+/// waiter needs its own place to register. A handle overwrites its slot on
+/// each `Poll::Pending`, so a waker left over from a dropped future or from a
+/// different task can't linger.
+pub struct WakerSlots<const N: usize> {
+    slots: [WakerSlot; N],
+}
+
+impl<const N: usize> WakerSlots<N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { WakerSlot::Free }; N],
+        }
+    }
+
+    /// Returns `None` when all `N` slots are taken.
+    pub fn claim(&mut self) -> Option<usize> {
+        let slot = self
+            .slots
+            .iter()
+            .position(|slot| matches!(slot, WakerSlot::Free))?;
+        self.slots[slot] = WakerSlot::Taken(None);
+        Some(slot)
+    }
+
+    pub fn release(&mut self, slot: usize) {
+        self.slots[slot] = WakerSlot::Free;
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| matches!(slot, WakerSlot::Taken(_)))
+            .count()
+    }
+
+    pub fn register(&mut self, slot: usize, waker: &Waker) {
+        match &mut self.slots[slot] {
+            WakerSlot::Taken(Some(current)) if current.will_wake(waker) => {}
+            WakerSlot::Taken(current) => *current = Some(waker.clone()),
+            WakerSlot::Free => panic!("Waker slot {} not claimed!", slot),
+        }
+    }
+
+    pub fn wake_all(&mut self) {
+        for slot in self.slots.iter_mut() {
+            if let WakerSlot::Taken(waker) = slot {
+                if let Some(waker) = waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for WakerSlots<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,121 @@
+use core::{cell::RefCell, future::poll_fn, task::Poll};
+
+use critical_section::Mutex;
+
+use crate::waker::WakerSlots;
+
+struct WatchState<T, const N: usize> {
+    value: Option<T>,
+    /// Bumped on every `send`, so receivers can tell if they're out of date.
+    version: u32,
+    receivers: WakerSlots<N>,
+}
+
+/// Shares the latest value with up to `N` receivers, each of which gets
+/// notified when it changes. Nothing is queued: a receiver that is slow to
+/// look will only ever see the most recent value.
+///
+/// Like `Signal`, the state lives behind a critical section, so a `Watch` can
+/// be a `static` updated from an interrupt handler.
+pub struct Watch<T, const N: usize> {
+    state: Mutex<RefCell<WatchState<T, N>>>,
+}
+
+impl<T: Clone, const N: usize> Watch<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(WatchState {
+                value: None,
+                version: 0,
+                receivers: WakerSlots::new(),
+            })),
+        }
+    }
+
+    pub fn send(&self, value: T) {
+        critical_section::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            state.value = Some(value);
+            state.version = state.version.wrapping_add(1);
+            state.receivers.wake_all();
+        });
+    }
+
+    pub fn get(&self) -> Option<T> {
+        critical_section::with(|cs| self.state.borrow_ref(cs).value.clone())
+    }
+
+    /// If a value has already been sent, a new receiver's first `changed()`
+    /// returns it straight away. Returns `None` if all `N` receiver slots are
+    /// in use.
+    pub fn get_receiver(&self) -> Option<WatchReceiver<'_, T, N>> {
+        let slot = critical_section::with(|cs| self.state.borrow_ref_mut(cs).receivers.claim())?;
+        Some(WatchReceiver {
+            watch: self,
+            slot,
+            seen_version: 0,
+        })
+    }
+}
+
+impl<T: Clone, const N: usize> Default for Watch<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct WatchReceiver<'a, T, const N: usize> {
+    watch: &'a Watch<T, N>,
+    slot: usize,
+    seen_version: u32,
+}
+
+impl<T: Clone, const N: usize> WatchReceiver<'_, T, N> {
+    /// Waits for a value this receiver hasn't seen yet.
+    pub async fn changed(&mut self) -> T {
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                let mut state = self.watch.state.borrow_ref_mut(cs);
+                match self.check(state.version, &state.value) {
+                    Some(value) => Poll::Ready(value),
+                    None => {
+                        state.receivers.register(self.slot, cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+
+    pub fn try_changed(&mut self) -> Option<T> {
+        critical_section::with(|cs| {
+            let state = self.watch.state.borrow_ref(cs);
+            self.check(state.version, &state.value)
+        })
+    }
+
+    pub fn get(&self) -> Option<T> {
+        self.watch.get()
+    }
+
+    fn check(&mut self, version: u32, value: &Option<T>) -> Option<T> {
+        if version == self.seen_version {
+            return None;
+        }
+        self.seen_version = version;
+        value.clone()
+    }
+}
+
+impl<T, const N: usize> Drop for WatchReceiver<'_, T, N> {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            self.watch
+                .state
+                .borrow_ref_mut(cs)
+                .receivers
+                .release(self.slot)
+        });
+    }
+}